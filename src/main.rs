@@ -1,9 +1,10 @@
 use clap::Parser;
 use std::{
+    collections::HashMap,
     fs::create_dir_all,
     path::{Path, PathBuf},
 };
-use tokio::fs;
+use tokio::{fs, io::AsyncReadExt};
 
 #[derive(Parser)]
 struct Opt {
@@ -46,11 +47,15 @@ struct Opt {
     )]
     ms: String,
 
-    #[arg(long, default_value = "https://i.imgur.com/oEKGtj4.png")]
-    corp_back: String,
+    /// Download the corp card back from here, e.g. https://i.imgur.com/oEKGtj4.png,
+    /// instead of taking it from the printable sheets
+    #[arg(long)]
+    corp_back: Option<String>,
 
-    #[arg(long, default_value = "https://i.imgur.com/UfL0Y0C.png")]
-    runner_back: String,
+    /// Download the runner card back from here, e.g. https://i.imgur.com/UfL0Y0C.png,
+    /// instead of taking it from the printable sheets
+    #[arg(long)]
+    runner_back: Option<String>,
 
     #[arg(short)]
     deck: Vec<String>,
@@ -58,14 +63,136 @@ struct Opt {
     #[arg(short)]
     tts: Vec<String>,
 
+    /// Add both sides' basic action cards, instead of just the decks' sides
     #[arg(long)]
     include_basic_actions: bool,
 
+    /// Don't add the basic action card for the decks' sides
+    #[arg(long)]
+    skip_basic_actions: bool,
+
     #[arg(long)]
     skip_ids: bool,
 
+    /// Add the mark cards even if no deck refers to marks
     #[arg(long)]
     include_marks: bool,
+
+    /// Don't add the mark cards for decks that refer to marks
+    #[arg(long)]
+    skip_marks: bool,
+
+    /// What to try, in order, for cards that have no cut image
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "text")]
+    fallback: Vec<Fallback>,
+
+    /// JSON file of owned cards, only the shortfall against it is printed
+    #[arg(long)]
+    collection: Option<PathBuf>,
+
+    /// Share one proxy pool across all decks, printing each card at the
+    /// highest count any single deck needs
+    #[arg(long)]
+    pool: bool,
+
+    /// Print every card in the pool this many times instead
+    #[arg(long)]
+    pool_total: Option<i64>,
+
+    /// Only print cards added or increased since this deck, and list the
+    /// removed ones. Passing the same NRDB ID as `-d` compares the cached
+    /// copy of the deck to its current version
+    #[arg(long)]
+    diff: Option<String>,
+
+    /// Skip proxies already printed on earlier runs, and record this run's in
+    /// the ledger
+    #[arg(long)]
+    ledger: bool,
+
+    /// Show the ledger of printed proxies and exit
+    #[arg(long)]
+    ledger_show: bool,
+
+    /// Change a ledger count and exit, e.g. `30010=-1`
+    #[arg(long)]
+    ledger_adjust: Vec<String>,
+
+    /// Empty the ledger and exit
+    #[arg(long)]
+    ledger_reset: bool,
+
+    /// Order cards on the print sheets, later keys break ties
+    #[arg(long, value_enum, value_delimiter = ',')]
+    sort: Vec<SortKey>,
+
+    /// Start a new page for each deck or card type
+    #[arg(long, value_enum)]
+    group: Option<Group>,
+
+    /// Start each deck on a fresh page, headed with its name and identity
+    #[arg(long)]
+    deck_labels: bool,
+
+    /// Print the deck name in small type on each card's margin
+    #[arg(long)]
+    deck_tags: bool,
+
+    /// Start with a cover sheet per deck listing its cards and totals
+    #[arg(long)]
+    cover_sheets: bool,
+
+    /// Write a tournament decklist registration sheet for these two decks
+    #[arg(long, num_args = 2, value_names = ["CORP", "RUNNER"])]
+    registration: Vec<String>,
+
+    /// Player name for the registration sheet
+    #[arg(long)]
+    player: Option<String>,
+
+    /// How to print the back face of double-faced cards listed in `faces.json`
+    #[arg(long, value_enum, default_value = "fold")]
+    faces: Faces,
+
+    /// Print a set's non-card pages (tokens, reference material, ...) as
+    /// listed in its `manifest.json`, as `<set>/<page>` or `<set>` for all
+    #[arg(long, value_delimiter = ',')]
+    extra: Vec<String>,
+
+    /// Check that the cuts of each set match their NRDB positions
+    #[arg(long)]
+    verify_positions: bool,
+
+    /// Write a contact sheet of each set's cuts, to check they're aligned
+    #[arg(long)]
+    contact_sheets: bool,
+
+    /// Check the cuts of each set for crop problems again, as is done when
+    /// they're first cut
+    #[arg(long)]
+    check_cuts: bool,
+
+    /// Report positions that are missing, extra or doubled in each pack's
+    /// cuts compared to NetrunnerDB, as is done when a set is first cut
+    #[arg(long)]
+    sets_report: bool,
+
+    /// Also export the printed cards as numbered front and back images with
+    /// bleed, for print shops such as MakePlayingCards
+    #[arg(long)]
+    print_shop: bool,
+
+    /// Bleed around each print shop card, in mm
+    #[arg(long, default_value_t = 3.0)]
+    bleed: f64,
+
+    /// Resolution of the print shop images
+    #[arg(long, default_value_t = 300)]
+    print_dpi: u32,
+
+    /// How the card edges are extended into the bleed
+    #[arg(long, value_enum, default_value = "mirror")]
+    bleed_mode: BleedMode,
 }
 
 #[tokio::main]
@@ -74,236 +201,2247 @@ async fn main() {
 
     fs::create_dir_all(&opt.card_dir).await.unwrap();
 
+    if opt.ledger_show || opt.ledger_reset || !opt.ledger_adjust.is_empty() {
+        update_ledger(&opt).await;
+        return;
+    }
+
+    // Sets cut on this run get their coverage checked
+    let mut uncut = Vec::new();
+    for set in PNP_SETS {
+        if !matches!(
+            fs::try_exists(opt.card_dir.join(set).join("cut")).await,
+            Ok(true)
+        ) {
+            uncut.push(set.to_string());
+        }
+    }
     acquire_system_gateway_set(&opt.card_dir.join("sg"), &opt.sg).await;
     acquire_system_update_set(&opt.card_dir.join("su21"), &opt.su).await;
     acquire_rebellion_without_reversal_set(&opt.card_dir.join("rwr"), &opt.rwr).await;
     acquire_the_automata_initiative_set(&opt.card_dir.join("tai"), &opt.tai).await;
     acquire_midnight_sun_set(&opt.card_dir.join("ms"), &opt.ms).await;
     acquire_parhelion_set(&opt.card_dir.join("ph"), &opt.ph).await;
-    acquire_card_back(&opt.card_dir.join("back").join("corp"), &opt.corp_back).await;
-    acquire_card_back(&opt.card_dir.join("back").join("runner"), &opt.runner_back).await;
+    if let Some(corp_back) = &opt.corp_back {
+        acquire_card_back(&opt.card_dir.join("back").join("corp"), corp_back).await;
+    }
+    if let Some(runner_back) = &opt.runner_back {
+        acquire_card_back(&opt.card_dir.join("back").join("runner"), runner_back).await;
+    }
+    extract_card_backs(&opt.card_dir, &["sg", "su21", "tai", "rwr"]).await;
+    import_overrides(&opt.card_dir.join("overrides")).await;
+    let cards = cards_by_position(&opt.card_dir).await;
+    if opt.verify_positions {
+        verify_positions(&opt.card_dir, &cards).await;
+    }
+    if opt.sets_report {
+        sets_report(&opt.card_dir, &cards, &cut_sets(&opt.card_dir).await).await;
+    } else if !uncut.is_empty() {
+        sets_report(&opt.card_dir, &cards, &uncut).await;
+    }
+    if opt.check_cuts {
+        for set in PNP_SETS {
+            check_cuts(&opt.card_dir.join(set).join("cut")).await;
+        }
+    }
+    if opt.contact_sheets {
+        for set in PNP_SETS {
+            build_contact_sheet(&opt.card_dir, &cards, set).await;
+        }
+    }
+
+    let faces = load_faces(&opt.card_dir).await;
+    let mut positions = HashMap::new();
+    for set in PNP_SETS {
+        positions.insert(set.to_string(), load_positions(&opt.card_dir, set).await);
+    }
+    build_documents(&opt, &opt.deck, &faces, &positions).await;
 
-    build_documents(
+    build_tts(
         &opt.card_dir,
-        opt.deck,
-        opt.include_basic_actions,
-        opt.include_marks,
-        opt.skip_ids,
+        &opt.tts,
+        &faces,
+        &positions,
+        &opt.fallback,
+        opt.collection.as_deref(),
     )
     .await;
 
-    build_tts(&opt.card_dir, opt.tts).await;
+    // After the builds, so NRDB images downloaded on this run are listed too
+    build_index(&opt.card_dir, &cards).await;
+
+    if let [corp, runner] = opt.registration.as_slice() {
+        build_registration(&opt, corp, runner).await;
+    }
 }
 
-async fn build_tts(path: &Path, tts: Vec<String>) {
+async fn build_tts(
+    path: &Path,
+    tts: &[String],
+    faces: &HashMap<String, PathBuf>,
+    positions: &HashMap<String, HashMap<i64, i64>>,
+    fallbacks: &[Fallback],
+    collection: Option<&Path>,
+) {
+    let collection = match collection {
+        Some(collection) => load_collection(collection).await,
+        None => HashMap::new(),
+    };
+    let mut skipped = Vec::new();
+    let mut unresolved = Vec::new();
     for deck in tts {
-        let deck_data = get_json_cached(
-            &path.join("cache"),
-            &format!("https://netrunnerdb.com/api/2.0/public/deck/{}", deck),
-        )
-        .await;
+        let deck = load_deck(path, deck, false).await;
+        let mut owned = collection.clone();
         let mut grid: Vec<Vec<PathBuf>> = vec![vec![]];
-        let cards = &deck_data["data"][0]["cards"].as_object().unwrap();
+        let mut double_faced = Vec::new();
         let mut side = String::new();
-        for (card, count) in cards.iter() {
-            let count = count.as_number().unwrap().as_i64().unwrap();
-            let card_data = get_json_cached(
-                &path.join("cache"),
-                &format!("https://netrunnerdb.com/api/2.0/public/card/{}", card),
-            )
-            .await;
-            let card_pack = &card_data["data"][0]["pack_code"].as_str().unwrap();
+        let mut identity = None;
+        for (card, count) in deck.cards.iter() {
+            let card = resolve_card(path, card, faces, positions, fallbacks).await;
+            if card.data.is_null() {
+                unresolved.push(card);
+                continue;
+            }
             if side.is_empty() {
-                side = card_data["data"][0]["side_code"]
-                    .as_str()
-                    .unwrap()
-                    .to_string();
+                side = card.data["side_code"].as_str().unwrap().to_string();
             }
-            let card_position = card_data["data"][0]["position"]
-                .as_number()
-                .unwrap()
-                .as_i64()
-                .unwrap();
+            if card.data["type_code"] == "identity" {
+                identity = Some(card.data.clone());
+            }
+            let count = take_owned(&mut owned, &card, *count, &mut skipped);
+            if count == 0 {
+                continue;
+            }
+            if card.fallback.is_some() {
+                unresolved.push(card.clone());
+            }
+            if card.back.is_some() && card.image.is_some() {
+                double_faced.push(card.clone());
+            }
+            let Some(image) = card.image else {
+                continue;
+            };
             for _ in 0..count {
                 let row = grid.last_mut().unwrap();
-                row.push(
-                    path.join(card_pack)
-                        .join("cut")
-                        .join(format!("c-{card_position:>03}.png")),
-                );
+                row.push(path.join(&image));
                 if row.len() == 10 {
                     grid.push(vec![]);
                 }
             }
         }
-        let last_row = grid.last_mut().unwrap();
-        while last_row.len() < 10 {
-            last_row.push(path.join("back").join(&side).join("back.png"));
+        let back = deck_back(path, &deck, identity.as_ref(), &side).await;
+        if !matches!(fs::try_exists(path.join(&back)).await, Ok(true)) {
+            panic!(
+                "{:?}, the card back of {}, doesn't exist, pass --{}-back with an image URL",
+                back, deck.label, side
+            );
+        }
+        let last_row = grid.last_mut().unwrap();
+        while last_row.len() < 10 {
+            last_row.push(path.join(&back));
+        }
+        let faces = tts_faces(path, &double_faced, &back);
+
+        let path = path.join("tts").join(&deck.label);
+        tokio::fs::create_dir_all(&path).await.unwrap();
+        if !double_faced.is_empty() {
+            fs::write(
+                path.join(format!("_-{}-faces.json", deck.label)),
+                serde_json::to_string_pretty(&faces).unwrap(),
+            )
+            .await
+            .unwrap();
+        }
+
+        let mut last_row: Option<PathBuf> = None;
+        'rows: for (col, paths) in grid.into_iter().enumerate() {
+            let row = path.join(format!("part-{col:>02}-XX.png"));
+            if matches!(fs::try_exists(&row).await, Ok(true)) {
+                println!("{:?} tts row already generated, skipping", row);
+                last_row = Some(row.clone());
+                continue 'rows;
+            }
+            let mut last_out: Option<PathBuf> = None;
+            'outs: for (row, card) in paths.into_iter().enumerate() {
+                let out = path.join(format!("part-{col:>02}-{row:>02}.png"));
+                if matches!(fs::try_exists(&out).await, Ok(true)) {
+                    println!("{:?} tts out already generated, skipping", out);
+                    last_out = Some(out.to_owned());
+                    continue 'outs;
+                }
+                // TTS sheets are kept at 1x whatever the resolution of the
+                // cards, as larger sheets go over its image size limit.
+                let sized = if row == 0 {
+                    out.clone()
+                } else {
+                    path.join(format!("card-{col:>02}-{row:>02}.png"))
+                };
+                resize_raw(&card, &sized, 744, 1031).await;
+                if !matches!(fs::try_exists(&sized).await, Ok(true)) {
+                    panic!("Couldn't resize {:?} for the TTS deck {}", card, deck.label);
+                }
+                if row > 0 {
+                    merge_raw(&last_out.unwrap(), &sized, &out, true).await;
+                    fs::remove_file(&sized).await.unwrap();
+                }
+                last_out = Some(out.to_owned());
+            }
+            if col == 0 {
+                tokio::fs::copy(dbg!(&last_out.unwrap()), dbg!(&row))
+                    .await
+                    .unwrap();
+            } else {
+                merge_raw(&last_row.unwrap(), &last_out.unwrap(), &row, false).await;
+            }
+            last_row = Some(row.clone())
+        }
+        tokio::fs::copy(
+            &last_row.unwrap(),
+            path.join(format!("_-{}.png", deck.label)),
+        )
+        .await
+        .unwrap();
+    }
+    report_skipped(&skipped, "already owned");
+    report_unresolved(&unresolved);
+}
+
+/// A TTS saved object with a card for each double-faced card, whose second
+/// state is the back face, so it can be flipped with the state switcher.
+fn tts_faces(path: &Path, cards: &[Card], back: &Path) -> serde_json::Value {
+    let url = |image: &Path| {
+        let image = std::fs::canonicalize(path.join(image)).unwrap();
+        format!(
+            "file:///{}",
+            image.display().to_string().trim_start_matches('/')
+        )
+    };
+    let object = |id: usize, title: &str, face: &Path| {
+        serde_json::json!({
+            "Name": "Card",
+            "Nickname": title,
+            "CardID": id * 100,
+            "Transform": {
+                "posX": 0, "posY": 1, "posZ": 0,
+                "rotX": 0, "rotY": 180, "rotZ": 180,
+                "scaleX": 1, "scaleY": 1, "scaleZ": 1
+            },
+            "CustomDeck": {
+                id.to_string(): {
+                    "FaceURL": url(face),
+                    "BackURL": url(back),
+                    "NumWidth": 1,
+                    "NumHeight": 1,
+                    "BackIsHidden": true,
+                    "UniqueBack": false
+                }
+            }
+        })
+    };
+    let objects: Vec<_> = cards
+        .iter()
+        .enumerate()
+        .map(|(i, card)| {
+            let title = card.data["title"].as_str().unwrap();
+            let mut front = object(2 * i + 1, title, card.image.as_ref().unwrap());
+            front["States"] = serde_json::json!({
+                "2": object(2 * i + 2, title, card.back.as_ref().unwrap())
+            });
+            front
+        })
+        .collect();
+    serde_json::json!({ "ObjectStates": objects })
+}
+
+async fn build_documents(
+    opt: &Opt,
+    decks: &[String],
+    faces: &HashMap<String, PathBuf>,
+    positions: &HashMap<String, HashMap<i64, i64>>,
+) {
+    let path = opt.card_dir.as_path();
+    let mut owned = match &opt.collection {
+        Some(collection) => load_collection(collection).await,
+        None => HashMap::new(),
+    };
+    let mut skipped = Vec::new();
+    let mut unresolved = Vec::new();
+    let previous = match &opt.diff {
+        Some(previous) => Some(load_deck(path, previous, false).await),
+        None => None,
+    };
+    let mut unchanged = Vec::new();
+    let mut current = Vec::new();
+    let mut ledger = load_ledger(path).await;
+    let mut shelf = if opt.ledger {
+        ledger.clone()
+    } else {
+        HashMap::new()
+    };
+    let mut shelved = Vec::new();
+    let mut labels = Vec::new();
+    let mut names = Vec::new();
+    let mut headers = Vec::new();
+    let mut covers = Vec::new();
+    let mut sides = Vec::new();
+    let mut backs = Vec::new();
+    let mut needs_marks = false;
+    let mut prints = Vec::new();
+    let mut exports = Vec::new();
+    for deck in decks.iter() {
+        // Diffing a deck against itself compares the cached copy to the
+        // current version on NetrunnerDB.
+        let refresh = opt.diff.as_ref() == Some(deck);
+        let deck = load_deck(path, deck, refresh).await;
+        labels.push(deck.label.clone());
+        names.push(deck.name.clone());
+        headers.push(deck.name.clone());
+        current.extend(deck.cards.iter().cloned());
+        let mut deck_list = Vec::new();
+        for (card, count) in deck.cards.iter() {
+            let card = resolve_card(path, card, faces, positions, &opt.fallback).await;
+            if card.data.is_null() {
+                unresolved.push(card);
+                continue;
+            }
+            deck_list.push((card.clone(), *count));
+            let card_type_code = card.data["type_code"].as_str().unwrap();
+            if card_type_code == "identity" {
+                let header = headers.last_mut().unwrap();
+                header.push_str(" - ");
+                header.push_str(card.data["title"].as_str().unwrap());
+            }
+            if card_type_code == "identity" && opt.skip_ids {
+                continue;
+            }
+            prints.push(Print {
+                deck: labels.len() - 1,
+                card,
+                count: *count,
+            });
+        }
+        for (card, _) in deck_list.iter() {
+            let side = card.data["side_code"].as_str().unwrap();
+            if !sides.contains(&side.to_string()) {
+                sides.push(side.to_string());
+            }
+            needs_marks |= refers_to_marks(&card.data);
+        }
+        let identity = deck_list
+            .iter()
+            .map(|(card, _)| &card.data)
+            .find(|data| data["type_code"] == "identity");
+        let side = deck_list
+            .first()
+            .map(|(card, _)| card.data["side_code"].as_str().unwrap())
+            .unwrap_or("runner");
+        backs.push((
+            side.to_string(),
+            deck_back(path, &deck, identity, side).await,
+        ));
+        if opt.cover_sheets {
+            covers.push(cover_page(&deck, &deck_list));
+        }
+    }
+    let (mut kept, removed) = match &previous {
+        Some(previous) => diff_counts(&previous.cards, &current),
+        None => (HashMap::new(), Vec::new()),
+    };
+    if opt.pool || opt.pool_total.is_some() {
+        prints = pool_decks(prints, opt.pool_total);
+    }
+    // Deck labels need every deck to start on its own page
+    let group_by = if opt.deck_labels {
+        Some(Group::Deck)
+    } else {
+        opt.group
+    };
+    sort_prints(&mut prints, &opt.sort, group_by);
+
+    let mut sheet = Sheet::new(opt.faces == Faces::Duplex);
+    for cover in covers {
+        sheet.push_page(&cover);
+    }
+    let mut group = None;
+    for Print { deck, card, count } in prints {
+        let count = take_owned(&mut kept, &card, count, &mut unchanged);
+        let count = take_owned(&mut owned, &card, count, &mut skipped);
+        let count = take_owned(&mut shelf, &card, count, &mut shelved);
+        if count == 0 {
+            continue;
+        }
+        if card.fallback.is_some() {
+            unresolved.push(card.clone());
+        }
+        let Some(image) = card.image else {
+            continue;
+        };
+        if opt.ledger {
+            *ledger.entry(card.code.clone()).or_default() += count;
+        }
+        let card_group = match group_by {
+            Some(Group::Deck) => Some(deck.to_string()),
+            Some(Group::Type) => Some(card.data["type_code"].as_str().unwrap().to_string()),
+            None => None,
+        };
+        if group.is_some() && group != card_group {
+            sheet.break_page();
+        }
+        group = card_group;
+        if opt.deck_labels {
+            sheet.header = Some(headers[deck].clone());
+        }
+        let tag = opt.deck_tags.then_some(names[deck].as_str());
+        let back = card.back.clone().unwrap_or_else(|| backs[deck].1.clone());
+        for _ in 0..count {
+            exports.push((image.clone(), back.clone()));
+            match (&card.back, opt.faces) {
+                (Some(face), Faces::Fold) => sheet.push_folded(&image, face, tag),
+                _ => sheet.push_image(&image, Some(&back), tag),
+            }
+        }
+    }
+    if opt.deck_labels {
+        sheet.break_page();
+        sheet.header = None;
+    }
+    // A diff only adds to decks that were printed with their extras already
+    let auto_extras = opt.diff.is_none();
+    // Extras get the back of the first deck on their side, custom or not
+    let extras_back = |side: &str| {
+        backs
+            .iter()
+            .find(|(deck_side, _)| deck_side == side)
+            .map(|(_, back)| back.clone())
+            .unwrap_or_else(|| side_back(side))
+    };
+    for (side, card_position) in BASIC_ACTIONS {
+        let for_deck = auto_extras && sides.iter().any(|s| s == side) && !opt.skip_basic_actions;
+        if opt.include_basic_actions || for_deck {
+            let image = PathBuf::from(format!("sg/cut/c-{:>03}.png", card_position));
+            let back = extras_back(side);
+            sheet.push_image(&image, Some(&back), None);
+            exports.push((image, back));
+        }
+    }
+    if opt.include_marks || (auto_extras && needs_marks && !opt.skip_marks) {
+        for card_position in MARKS {
+            let image = PathBuf::from(format!("ms/cut/c-{:>03}.png", card_position));
+            let back = extras_back("runner");
+            sheet.push_image(&image, Some(&back), None);
+            exports.push((image, back));
+        }
+    }
+    for selection in opt.extra.iter() {
+        for extra in find_extras(path, selection).await {
+            sheet.push_page(&format!(
+                "<img class=\"full\" src=\"{}\" />",
+                extra.display()
+            ));
+        }
+    }
+    let document = sheet.finish();
+
+    let mut name = labels.join("_");
+    if let Some(previous) = previous {
+        name = format!("{}_diff_{}", previous.label, name);
+        let mut removals = String::new();
+        for (code, count) in removed {
+            let card = get_card(path, &code).await;
+            removals.push_str(&format!(
+                "{}x {} ({})\n",
+                count,
+                card["title"].as_str().unwrap_or("not on NetrunnerDB"),
+                code
+            ));
+        }
+        println!("Cards removed since {}:\n{}", previous.label, removals);
+        fs::write(path.join(format!("{}.removed.txt", name)), removals)
+            .await
+            .unwrap();
+        report_skipped(&unchanged, &format!("unchanged since {}", previous.label));
+    }
+    fs::write(path.join(format!("{}.html", name)), document)
+        .await
+        .unwrap();
+    if opt.print_shop && !decks.is_empty() {
+        export_print_shop(opt, &name, &exports).await;
+    }
+    if opt.ledger {
+        save_ledger(path, &ledger).await;
+    }
+    report_skipped(&skipped, "already owned");
+    report_skipped(&shelved, "already printed on an earlier run");
+    report_unresolved(&unresolved);
+}
+
+/// How print shop cards are extended into the bleed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+enum BleedMode {
+    /// Reflect the card art outwards at each edge
+    Mirror,
+    /// Stretch the outermost pixels outwards
+    Edge,
+}
+
+/// Writes each printed card's front and back to `print-shop/<name>` as
+/// `front-NNN.png` and `back-NNN.png`, at the card size plus bleed, for
+/// uploading to a print shop.
+async fn export_print_shop(opt: &Opt, name: &str, cards: &[(PathBuf, PathBuf)]) {
+    if name.is_empty() || cards.is_empty() {
+        println!("No cards to export for print shops");
+        return;
+    }
+    let path = opt.card_dir.as_path();
+    let to = path.join("print-shop").join(name);
+    // Numbering changes with the cards, so don't leave stale images behind
+    if matches!(fs::try_exists(&to).await, Ok(true)) {
+        fs::remove_dir_all(&to).await.unwrap();
+    }
+    fs::create_dir_all(&to).await.unwrap();
+    let pixels = |mm: f64| (mm / 25.4 * opt.print_dpi as f64).round() as u32;
+    let (width, height) = (pixels(63.0), pixels(88.0));
+    let bleed = pixels(opt.bleed);
+    // Most cards share a back, and decks often have copies of a card
+    let mut rendered: HashMap<&Path, PathBuf> = HashMap::new();
+    for (i, (front, back)) in cards.iter().enumerate() {
+        for (image, side) in [(front, "front"), (back, "back")] {
+            let out = to.join(format!("{}-{:>03}.png", side, i + 1));
+            match rendered.get(image.as_path()) {
+                Some(first) => {
+                    fs::copy(first, &out).await.unwrap();
+                }
+                None => {
+                    bleed_raw(&path.join(image), &out, width, height, bleed, opt).await;
+                    rendered.insert(image, out);
+                }
+            }
+        }
+    }
+    println!(
+        "{} cards exported to {:?} at {}x{} px",
+        cards.len(),
+        to,
+        width + 2 * bleed,
+        height + 2 * bleed
+    );
+}
+
+/// Resizes a card to `width` by `height` and extends it by `bleed` on every
+/// side, mirroring or stretching its edges as set by `--bleed-mode`.
+async fn bleed_raw(from: &Path, to: &Path, width: u32, height: u32, bleed: u32, opt: &Opt) {
+    let virtual_pixel = match opt.bleed_mode {
+        BleedMode::Mirror => "Mirror",
+        BleedMode::Edge => "Edge",
+    };
+    tokio::process::Command::new("magick")
+        .arg("convert")
+        .arg(from)
+        .arg("-filter")
+        .arg("Lanczos")
+        .arg("-resize")
+        .arg(format!("{}x{}!", width, height))
+        .arg("-virtual-pixel")
+        .arg(virtual_pixel)
+        .arg("-set")
+        .arg("option:distort:viewport")
+        .arg(format!(
+            "{}x{}-{}-{}",
+            width + 2 * bleed,
+            height + 2 * bleed,
+            bleed,
+            bleed
+        ))
+        .arg("-distort")
+        .arg("SRT")
+        .arg("0")
+        .arg("+repage")
+        .arg("-units")
+        .arg("PixelsPerInch")
+        .arg("-density")
+        .arg(opt.print_dpi.to_string())
+        .arg(to)
+        .spawn()
+        .unwrap()
+        .wait()
+        .await
+        .unwrap();
+}
+
+/// Compares the cards of a previous deck with the current decks' cards.
+///
+/// Returns the previous counts, which are taken off the current counts like
+/// owned cards so only what was added or increased gets printed, and the cards
+/// that were removed or decreased, in the previous deck's order.
+fn diff_counts(
+    previous: &[(String, i64)],
+    current: &[(String, i64)],
+) -> (HashMap<String, i64>, Vec<(String, i64)>) {
+    let kept: HashMap<String, i64> = previous.iter().cloned().collect();
+    let mut remaining = kept.clone();
+    for (card, count) in current {
+        if let Some(remaining) = remaining.get_mut(card) {
+            *remaining -= count;
+        }
+    }
+    let removed = previous
+        .iter()
+        .map(|(card, _)| (card.clone(), remaining[card]))
+        .filter(|(_, count)| *count > 0)
+        .collect();
+    (kept, removed)
+}
+
+/// Positions of the basic action cards in System Gateway, which like the
+/// rest of the set lists the runner before the corp.
+const BASIC_ACTIONS: [(&str, i64); 2] = [("runner", 78), ("corp", 79)];
+
+/// Positions of the mark cards in Midnight Sun.
+const MARKS: std::ops::RangeInclusive<i64> = 66..=68;
+
+/// Whether the card's text refers to the runner's mark, so the deck needs the
+/// mark cards to play.
+fn refers_to_marks(data: &serde_json::Value) -> bool {
+    data["text"]
+        .as_str()
+        .unwrap_or_default()
+        .split(|c: char| !c.is_alphabetic())
+        .any(|word| word.eq_ignore_ascii_case("mark"))
+}
+
+/// A card to print, and the index of the deck it is printed for.
+struct Print {
+    deck: usize,
+    card: Card,
+    count: i64,
+}
+
+/// Merges several decks into one proxy pool, each card at the highest count
+/// any single deck needs, or at `total` copies when given. Pooled cards
+/// belong to the first deck that uses them.
+fn pool_decks(prints: Vec<Print>, total: Option<i64>) -> Vec<Print> {
+    let mut pool: Vec<Print> = Vec::new();
+    for print in prints {
+        match pool
+            .iter_mut()
+            .find(|pooled| pooled.card.code == print.card.code)
+        {
+            Some(pooled) => pooled.count = pooled.count.max(print.count),
+            None => pool.push(print),
+        }
+    }
+    if let Some(total) = total {
+        for print in pool.iter_mut() {
+            print.count = total;
+        }
+    }
+    pool
+}
+
+/// Order in which card types are printed when sorting by type.
+const TYPE_ORDER: &[&str] = &[
+    "identity",
+    "agenda",
+    "asset",
+    "upgrade",
+    "operation",
+    "ice",
+    "event",
+    "hardware",
+    "resource",
+    "program",
+];
+
+/// How to print the back face of double-faced cards.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+enum Faces {
+    /// Next to the front in the same row, to fold along the shared edge
+    Fold,
+    /// On a page of backs after each page of fronts, for two-sided printing,
+    /// with single-faced cards getting their side's card back
+    Duplex,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+enum SortKey {
+    Type,
+    Faction,
+    /// Card code, which orders cards by set in release order and by
+    /// position within it
+    Set,
+    Title,
+    Cost,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+enum Group {
+    Deck,
+    Type,
+}
+
+/// Sorts the cards to print by the group first, so each group is contiguous,
+/// then by each sort key in turn. Ties keep their deck order.
+fn sort_prints(prints: &mut [Print], keys: &[SortKey], group: Option<Group>) {
+    let type_rank = |print: &Print| {
+        let type_code = print.card.data["type_code"].as_str().unwrap_or_default();
+        TYPE_ORDER
+            .iter()
+            .position(|t| *t == type_code)
+            .unwrap_or(TYPE_ORDER.len())
+    };
+    let compare = |a: &Print, b: &Print, key: SortKey| {
+        let (a_data, b_data) = (&a.card.data, &b.card.data);
+        match key {
+            SortKey::Type => type_rank(a).cmp(&type_rank(b)),
+            SortKey::Faction => a_data["faction_code"]
+                .as_str()
+                .cmp(&b_data["faction_code"].as_str()),
+            SortKey::Set => a.card.code.cmp(&b.card.code),
+            SortKey::Title => a_data["title"].as_str().cmp(&b_data["title"].as_str()),
+            // Cards without a cost go last
+            SortKey::Cost => a_data["cost"]
+                .as_i64()
+                .unwrap_or(i64::MAX)
+                .cmp(&b_data["cost"].as_i64().unwrap_or(i64::MAX)),
+        }
+    };
+    prints.sort_by(|a, b| {
+        let grouped = match group {
+            Some(Group::Deck) => a.deck.cmp(&b.deck),
+            Some(Group::Type) => compare(a, b, SortKey::Type),
+            None => std::cmp::Ordering::Equal,
+        };
+        keys.iter()
+            .fold(grouped, |ordering, key| ordering.then(compare(a, b, *key)))
+    });
+}
+
+/// The print sheet HTML, nine cards to an A4 page.
+struct Sheet {
+    document: String,
+    index: usize,
+    /// Whether a page of cards has been started and not yet closed.
+    open: bool,
+    /// Printed at the top of each new page.
+    header: Option<String>,
+    /// Follow every page of cards with a page of their backs.
+    duplex: bool,
+    /// Backs of the cards on the current page, for duplex printing.
+    backs: Vec<Option<PathBuf>>,
+}
+
+impl Sheet {
+    fn new(duplex: bool) -> Sheet {
+        let mut document = String::new();
+        document.push_str("<!DOCTYPE html>\n");
+        document.push_str("<html lang=\"en\"><head><meta charset=\"UTF-8\"><meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\"><title>PDF</title><style>*,::after,::before{margin:0;padding:0;min-width:0}.page{width:210mm;height:297mm;display:grid;place-items:center;position:relative}.imgs{display:grid;grid-template-columns:63mm 63mm 63mm;grid-template-rows:88mm 88mm 88mm;gap:0.5pt}img{width:100%;height:100%}.header{position:absolute;top:5mm;width:100%;text-align:center;font:10pt sans-serif}.card{position:relative}.tag{position:absolute;bottom:0.8mm;right:3mm;font:4pt sans-serif;color:#fff;background:#0008;padding:0 0.5mm}.sheet{display:block;padding:15mm;font:10pt sans-serif;box-sizing:border-box}.sheet .full{object-fit:contain}.sheet h1{font-size:18pt}.sheet h2{font-size:11pt;margin-top:4mm}.sheet p{margin-top:2mm}.list{columns:2;column-gap:10mm}.list section{break-inside:avoid}.list li{list-style:none}</style></head><body>");
+        Sheet {
+            document,
+            index: 0,
+            open: false,
+            header: None,
+            duplex,
+            backs: Vec::new(),
+        }
+    }
+
+    /// Adds a card, with a small tag printed on its bottom margin if given.
+    /// The back is only used when printing duplex.
+    fn push_image(&mut self, image: &Path, back: Option<&Path>, tag: Option<&str>) {
+        self.open_page();
+        match tag {
+            Some(tag) => self.document.push_str(&format!(
+                "<div class=\"card\"><img src=\"{}\" /><span class=\"tag\">{}</span></div>",
+                image.display(),
+                escape_html(tag)
+            )),
+            None => self
+                .document
+                .push_str(&format!("<img src=\"{}\" />", image.display())),
+        }
+        self.backs.push(back.map(Path::to_path_buf));
+        self.index += 1;
+    }
+
+    /// Adds both faces of a double-faced card next to each other in the same
+    /// row, to fold along the shared edge.
+    fn push_folded(&mut self, front: &Path, back: &Path, tag: Option<&str>) {
+        if self.index % 3 == 2 {
+            self.push_blank();
+        }
+        self.push_image(front, None, tag);
+        self.push_image(back, None, tag);
+    }
+
+    /// Leaves a cell empty.
+    fn push_blank(&mut self) {
+        self.open_page();
+        self.document.push_str("<div></div>");
+        self.backs.push(None);
+        self.index += 1;
+    }
+
+    /// Moves on to a fresh page, unless the current one is still empty.
+    fn break_page(&mut self) {
+        if !self.index.is_multiple_of(9) {
+            self.index += 9 - self.index % 9;
+        }
+    }
+
+    /// Adds a full page of other content, such as a deck cover sheet.
+    fn push_page(&mut self, html: &str) {
+        self.close_page();
+        self.break_page();
+        self.document
+            .push_str(&format!("<div class=\"page sheet\">{}</div>", html));
+        if self.duplex {
+            self.document.push_str("<div class=\"page\"></div>");
+        }
+    }
+
+    fn open_page(&mut self) {
+        if !self.index.is_multiple_of(9) {
+            return;
+        }
+        self.close_page();
+        self.open = true;
+        self.document.push_str("<div class=\"page\">");
+        if let Some(header) = &self.header {
+            self.document
+                .push_str(&format!("<p class=\"header\">{}</p>", escape_html(header)));
+        }
+        self.document.push_str("<div class=\"imgs\">");
+    }
+
+    fn close_page(&mut self) {
+        if !self.open {
+            return;
+        }
+        self.document.push_str("</div></div>");
+        self.open = false;
+        if self.duplex {
+            // Mirror each row, so the backs line up when flipped on the long edge
+            self.document
+                .push_str("<div class=\"page\"><div class=\"imgs\">");
+            for row in 0..3 {
+                for col in (0..3).rev() {
+                    match self.backs.get(row * 3 + col) {
+                        Some(Some(back)) => self
+                            .document
+                            .push_str(&format!("<img src=\"{}\" />", back.display())),
+                        _ => self.document.push_str("<div></div>"),
+                    }
+                }
+            }
+            self.document.push_str("</div></div>");
+        }
+        self.backs.clear();
+    }
+
+    fn finish(mut self) -> String {
+        self.close_page();
+        self.document.push_str("</body></html>");
+        self.document
+    }
+}
+
+/// Writes a tournament decklist registration sheet for a corp and runner deck,
+/// one page per side.
+async fn build_registration(opt: &Opt, corp: &str, runner: &str) {
+    let path = opt.card_dir.as_path();
+    let mut sheet = Sheet::new(false);
+    let mut labels = Vec::new();
+    for (side, deck) in [("corp", corp), ("runner", runner)] {
+        let deck = load_deck(path, deck, false).await;
+        labels.push(deck.label.clone());
+        let mut cards = Vec::new();
+        for (code, count) in deck.cards.iter() {
+            let card = Card {
+                code: code.clone(),
+                data: get_card(path, code).await,
+                image: None,
+                back: None,
+                fallback: None,
+            };
+            if card.data["side_code"] != side {
+                println!(
+                    "{} in {} is not a {} card",
+                    card.data["title"].as_str().unwrap(),
+                    deck.label,
+                    side
+                );
+            }
+            cards.push((card, *count));
+        }
+        let identity = cards
+            .iter()
+            .find(|(card, _)| card.data["type_code"] == "identity")
+            .map(|(card, _)| card.data["title"].as_str().unwrap())
+            .unwrap_or_default();
+        let mut html = String::from("<h1>Decklist registration</h1>");
+        html.push_str(&format!(
+            "<p>Player: {}</p><p>Event: ____________________ Date: ____________</p>",
+            escape_html(opt.player.as_deref().unwrap_or("____________________"))
+        ));
+        html.push_str(&format!(
+            "<h2>{} identity: {}</h2><p>Deck: {}</p>",
+            capitalise(side),
+            escape_html(identity),
+            escape_html(&deck.name)
+        ));
+        html.push_str(&decklist_html(&cards));
+        sheet.push_page(&html);
+    }
+    fs::write(
+        path.join(format!("registration-{}.html", labels.join("-"))),
+        sheet.finish(),
+    )
+    .await
+    .unwrap();
+}
+
+/// A summary page for a deck: name, identity, NRDB link and decklist.
+fn cover_page(deck: &Deck, cards: &[(Card, i64)]) -> String {
+    let mut html = format!("<h1>{}</h1>", escape_html(&deck.name));
+    if let Some((identity, _)) = cards
+        .iter()
+        .find(|(card, _)| card.data["type_code"] == "identity")
+    {
+        html.push_str(&format!(
+            "<h2>{}</h2>",
+            escape_html(identity.data["title"].as_str().unwrap())
+        ));
+    }
+    if let Some(id) = &deck.nrdb_id {
+        html.push_str(&format!(
+            "<p>NetrunnerDB deck {id}<br />https://netrunnerdb.com/en/deck/view/{id}</p>"
+        ));
+    }
+    html.push_str(&decklist_html(cards));
+    html
+}
+
+/// The cards of a deck grouped by type with counts, followed by the deck
+/// size, influence and agenda point totals.
+fn decklist_html(cards: &[(Card, i64)]) -> String {
+    let identity = cards
+        .iter()
+        .map(|(card, _)| &card.data)
+        .find(|data| data["type_code"] == "identity");
+    let faction = identity.and_then(|data| data["faction_code"].as_str());
+
+    let mut html = String::from("<div class=\"list\">");
+    let mut types: Vec<&str> = cards
+        .iter()
+        .map(|(card, _)| card.data["type_code"].as_str().unwrap())
+        .filter(|type_code| *type_code != "identity")
+        .collect();
+    types.sort_by_key(|type_code| {
+        TYPE_ORDER
+            .iter()
+            .position(|t| t == type_code)
+            .unwrap_or(TYPE_ORDER.len())
+    });
+    types.dedup();
+    let (mut size, mut influence, mut agenda_points) = (0, 0, 0);
+    for type_code in types {
+        let mut of_type: Vec<_> = cards
+            .iter()
+            .filter(|(card, _)| card.data["type_code"] == type_code)
+            .collect();
+        of_type.sort_by_key(|(card, _)| card.data["title"].as_str().unwrap());
+        let total: i64 = of_type.iter().map(|(_, count)| count).sum();
+        html.push_str(&format!(
+            "<section><h2>{} ({})</h2><ul>",
+            capitalise(type_code),
+            total
+        ));
+        for (card, count) in of_type {
+            let data = &card.data;
+            size += count;
+            agenda_points += count * data["agenda_points"].as_i64().unwrap_or(0);
+            let mut pips = String::new();
+            if data["faction_code"].as_str() != faction {
+                let cost = count * data["faction_cost"].as_i64().unwrap_or(0);
+                influence += cost;
+                pips = " &bull;".repeat(cost as usize);
+            }
+            html.push_str(&format!(
+                "<li>{}x {}{}</li>",
+                count,
+                escape_html(data["title"].as_str().unwrap()),
+                pips
+            ));
+        }
+        html.push_str("</ul></section>");
+    }
+    html.push_str("</div><p>");
+    match identity.and_then(|data| data["minimum_deck_size"].as_i64()) {
+        Some(minimum) => html.push_str(&format!("{} cards (minimum {})", size, minimum)),
+        None => html.push_str(&format!("{} cards", size)),
+    }
+    match identity.and_then(|data| data["influence_limit"].as_i64()) {
+        Some(limit) => html.push_str(&format!("<br />Influence {}/{}", influence, limit)),
+        None => html.push_str(&format!("<br />Influence {}", influence)),
+    }
+    if agenda_points > 0 {
+        html.push_str(&format!("<br />{} agenda points", agenda_points));
+    }
+    html.push_str("</p>");
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A deck to proxy, loaded from NetrunnerDB, a local file or stdin.
+struct Deck {
+    /// Name used for output files, the NRDB ID or the file stem.
+    label: String,
+    /// The deck's name on NRDB, or the label if it doesn't have one.
+    name: String,
+    nrdb_id: Option<String>,
+    cards: Vec<(String, i64)>,
+}
+
+/// Loads a deck given on the command line.
+///
+/// `-` reads from stdin, an existing file path is read from disk and anything
+/// else is treated as a NetrunnerDB deck ID. Local decks may either be in the
+/// NRDB deck JSON shape or a plain `{code: count}` map. NetrunnerDB decks are
+/// fetched again instead of read from the cache when `refresh` is set.
+async fn load_deck(path: &Path, deck: &str, refresh: bool) -> Deck {
+    if deck == "-" {
+        // Stdin can only be read once, but `-` may be passed to several of
+        // `-d`, `-t` and `--diff`
+        static STDIN: tokio::sync::OnceCell<serde_json::Value> = tokio::sync::OnceCell::const_new();
+        let json = STDIN
+            .get_or_init(|| async {
+                let mut read = Vec::new();
+                tokio::io::stdin().read_to_end(&mut read).await.unwrap();
+                serde_json::from_slice(&read).unwrap()
+            })
+            .await;
+        return parse_deck("stdin", json);
+    }
+    let file = Path::new(deck);
+    if matches!(fs::try_exists(file).await, Ok(true)) {
+        let read = fs::read(file).await.unwrap();
+        let label = file.file_stem().unwrap().to_str().unwrap();
+        return parse_deck(label, &serde_json::from_slice(&read).unwrap());
+    }
+    let url = format!("https://netrunnerdb.com/api/2.0/public/deck/{}", deck);
+    let deck_data = if refresh {
+        get_json_refreshed(&path.join("cache"), &url).await
+    } else {
+        get_json_cached(&path.join("cache"), &url).await
+    };
+    let mut deck_data = parse_deck(deck, &deck_data);
+    deck_data.nrdb_id = Some(deck.to_string());
+    deck_data
+}
+
+fn parse_deck(label: &str, json: &serde_json::Value) -> Deck {
+    let data = if json["data"].is_array() {
+        &json["data"][0]
+    } else {
+        json
+    };
+    let (name, cards) = match data["cards"].as_object() {
+        Some(cards) => (data["name"].as_str().unwrap_or(label), cards),
+        None => (label, data.as_object().unwrap()),
+    };
+    Deck {
+        label: label.to_string(),
+        name: name.to_string(),
+        // The NRDB API gives deck IDs as numbers
+        nrdb_id: match &data["id"] {
+            serde_json::Value::Number(id) => Some(id.to_string()),
+            id => id.as_str().map(str::to_string),
+        },
+        cards: cards
+            .iter()
+            .map(|(code, count)| (code.clone(), count.as_i64().unwrap()))
+            .collect(),
+    }
+}
+
+/// What to do with a card that has no cut image in any acquired set.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+enum Fallback {
+    /// Download the card image from NetrunnerDB into the pack's cut folder
+    Nrdb,
+    /// Render a proxy from the card's NRDB title, stats and text
+    Text,
+    /// Print a blank card with the title, so the deck still has the right count
+    Placeholder,
+    /// Leave the card out entirely
+    Skip,
+}
+
+/// A deck card resolved to the image it will be printed with.
+#[derive(Clone)]
+struct Card {
+    code: String,
+    /// The card's entry in the NRDB card API.
+    data: serde_json::Value,
+    /// Image relative to the card dir, `None` if the card can't be printed.
+    image: Option<PathBuf>,
+    /// Image of the back face, for double-faced cards.
+    back: Option<PathBuf>,
+    /// Set when there was no cut image and a fallback was used instead.
+    fallback: Option<Fallback>,
+}
+
+async fn get_card(path: &Path, code: &str) -> serde_json::Value {
+    let card_data = get_json_cached(
+        &path.join("cache"),
+        &format!("https://netrunnerdb.com/api/2.0/public/card/{}", code),
+    )
+    .await;
+    card_data["data"][0].clone()
+}
+
+/// Finds the image for a card, preferring an imported override to the cut
+/// from the acquired sets, and trying each fallback in turn when there's
+/// neither.
+async fn resolve_card(
+    path: &Path,
+    code: &str,
+    faces: &HashMap<String, PathBuf>,
+    positions: &HashMap<String, HashMap<i64, i64>>,
+    fallbacks: &[Fallback],
+) -> Card {
+    let data = get_card(path, code).await;
+    let (Some(card_pack), Some(card_position)) = (
+        data["pack_code"].as_str().map(str::to_string),
+        data["position"].as_i64(),
+    ) else {
+        // Not a card NetrunnerDB knows, such as a typo in a local deck
+        return Card {
+            code: code.to_string(),
+            data: serde_json::Value::Null,
+            image: None,
+            back: None,
+            fallback: None,
+        };
+    };
+    let cut_position = positions
+        .get(&card_pack)
+        .and_then(|positions| positions.get(&card_position))
+        .copied()
+        .unwrap_or(card_position);
+    let cut = PathBuf::from(&card_pack)
+        .join("cut")
+        .join(format!("c-{:>03}.png", cut_position));
+    let overrides = [
+        PathBuf::from("overrides")
+            .join("cut")
+            .join(format!("{}.png", code)),
+        PathBuf::from("overrides")
+            .join("cut")
+            .join(&card_pack)
+            .join(format!("c-{:>03}.png", card_position)),
+    ];
+    let mut card = Card {
+        code: code.to_string(),
+        data,
+        image: None,
+        back: faces.get(code).cloned(),
+        fallback: None,
+    };
+    for image in overrides.into_iter().chain([cut.clone()]) {
+        if matches!(fs::try_exists(path.join(&image)).await, Ok(true)) {
+            card.image = Some(image);
+            return card;
+        }
+    }
+    for fallback in fallbacks {
+        card.fallback = Some(*fallback);
+        card.image = match fallback {
+            Fallback::Nrdb => nrdb_image(path, &card, &cut).await,
+            Fallback::Text => Some(text_image(path, &card).await),
+            Fallback::Placeholder => Some(placeholder_image(path, &card).await),
+            Fallback::Skip => None,
+        };
+        if card.image.is_some() || *fallback == Fallback::Skip {
+            break;
+        }
+    }
+    card
+}
+
+/// Back face images of double-faced cards, from `faces.json` in the card dir
+/// which maps card codes to images relative to it. Entries whose image doesn't
+/// exist are reported and ignored.
+async fn load_faces(path: &Path) -> HashMap<String, PathBuf> {
+    let faces: HashMap<String, PathBuf> = match fs::read(path.join("faces.json")).await {
+        Ok(read) => serde_json::from_slice(&read).unwrap(),
+        Err(_) => return HashMap::new(),
+    };
+    let mut found = HashMap::new();
+    for (code, face) in faces {
+        if matches!(fs::try_exists(path.join(&face)).await, Ok(true)) {
+            found.insert(code, face);
+        } else {
+            println!(
+                "{:?}, the back face of {} in faces.json, doesn't exist, ignoring it",
+                face, code
+            );
+        }
+    }
+    found
+}
+
+/// The card back for a side, relative to the card dir.
+fn side_back(side: &str) -> PathBuf {
+    PathBuf::from("back").join(side).join("back.png")
+}
+
+/// The card back for a deck, relative to the card dir.
+///
+/// Custom backs are looked up as `back/deck/<deck label>.png`, then
+/// `back/identity/<identity code>.png` and `back/faction/<faction code>.png`,
+/// falling back to the side's back. Custom backs are resized to the card size
+/// into `back/sized`.
+async fn deck_back(
+    path: &Path,
+    deck: &Deck,
+    identity: Option<&serde_json::Value>,
+    side: &str,
+) -> PathBuf {
+    let mut custom = vec![PathBuf::from("deck").join(format!("{}.png", deck.label))];
+    if let Some(identity) = identity {
+        let code = identity["code"].as_str().unwrap();
+        let faction = identity["faction_code"].as_str().unwrap();
+        custom.push(PathBuf::from("identity").join(format!("{}.png", code)));
+        custom.push(PathBuf::from("faction").join(format!("{}.png", faction)));
+    }
+    for back in custom {
+        let from = path.join("back").join(&back);
+        if !matches!(fs::try_exists(&from).await, Ok(true)) {
+            continue;
+        }
+        let sized = PathBuf::from("back").join("sized").join(&back);
+        // Resize again whenever the custom back changes
+        if let (Ok(from), Ok(to)) = (
+            fs::metadata(&from).await,
+            fs::metadata(path.join(&sized)).await,
+        ) {
+            if to.modified().unwrap() >= from.modified().unwrap() {
+                return sized;
+            }
+        }
+        fs::create_dir_all(path.join(&sized).parent().unwrap())
+            .await
+            .unwrap();
+        resize_raw(&from, &path.join(&sized), 744, 1031).await;
+        return sized;
+    }
+    side_back(side)
+}
+
+/// Downloads the card image through NRDB's image URL template and upscales it
+/// into the pack's cut folder, so later runs pick it up like a PNP cut.
+async fn nrdb_image(path: &Path, card: &Card, cut: &Path) -> Option<PathBuf> {
+    let download = download_nrdb_image(path, &card.data).await?;
+    fs::create_dir_all(path.join(cut).parent().unwrap())
+        .await
+        .unwrap();
+    resize_raw(&download, &path.join(cut), 744, 1031).await;
+    Some(cut.to_path_buf())
+}
+
+/// Downloads a card's image from NetrunnerDB into `<pack>/download/nrdb`,
+/// unless it's there already.
+async fn download_nrdb_image(path: &Path, data: &serde_json::Value) -> Option<PathBuf> {
+    let code = data["code"].as_str().unwrap();
+    let card_data = get_json_cached(
+        &path.join("cache"),
+        &format!("https://netrunnerdb.com/api/2.0/public/card/{}", code),
+    )
+    .await;
+    let url = match data["image_url"].as_str() {
+        Some(url) => url.to_string(),
+        None => card_data["imageUrlTemplate"]
+            .as_str()?
+            .replace("{code}", code),
+    };
+    let download = path
+        .join(data["pack_code"].as_str().unwrap())
+        .join("download")
+        .join("nrdb");
+    fs::create_dir_all(&download).await.unwrap();
+    let download = download.join(url.rsplit('/').next().unwrap());
+    if !matches!(fs::try_exists(&download).await, Ok(true)) {
+        let response = match reqwest::get(&url).await {
+            Ok(response) => response,
+            Err(error) => {
+                println!("Couldn't download the image of {}: {}", code, error);
+                return None;
+            }
+        };
+        if !response.status().is_success() {
+            println!(
+                "{} has no image on NetrunnerDB ({})",
+                code,
+                response.status()
+            );
+            return None;
+        }
+        let bytes = match response.bytes().await {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                println!("Couldn't download the image of {}: {}", code, error);
+                return None;
+            }
+        };
+        fs::write(&download, bytes).await.unwrap();
+    }
+    Some(download)
+}
+
+async fn placeholder_image(path: &Path, card: &Card) -> PathBuf {
+    let image = PathBuf::from("placeholder").join(format!("{}.png", card.code));
+    if matches!(fs::try_exists(path.join(&image)).await, Ok(true)) {
+        return image;
+    }
+    fs::create_dir_all(path.join("placeholder")).await.unwrap();
+    tokio::process::Command::new("magick")
+        .arg("-size")
+        .arg("744x1031")
+        .arg("-background")
+        .arg("white")
+        .arg("-gravity")
+        .arg("center")
+        .arg("-pointsize")
+        .arg("48")
+        .arg(format!(
+            "caption:{}\n\n{}\nno proxy image available",
+            escape_magick_text(card.data["title"].as_str().unwrap()),
+            card.code
+        ))
+        .arg(path.join(&image))
+        .spawn()
+        .unwrap()
+        .wait()
+        .await
+        .unwrap();
+    image
+}
+
+/// Renders a card-sized proxy from the card's NRDB fields, for cards without
+/// any printable image.
+async fn text_image(path: &Path, card: &Card) -> PathBuf {
+    let image = PathBuf::from("text").join(format!("{}.png", card.code));
+    if matches!(fs::try_exists(path.join(&image)).await, Ok(true)) {
+        return image;
+    }
+    fs::create_dir_all(path.join("text")).await.unwrap();
+    let data = &card.data;
+    let colour = faction_colour(data["faction_code"].as_str().unwrap_or_default());
+
+    let mut title = data["title"].as_str().unwrap().to_string();
+    if data["uniqueness"].as_bool() == Some(true) {
+        title.insert_str(0, "* ");
+    }
+    let mut type_line = capitalise(&data["type_code"].as_str().unwrap().replace('-', " "));
+    if let Some(keywords) = data["keywords"].as_str() {
+        type_line.push_str(": ");
+        type_line.push_str(keywords);
+    }
+    let mut faction_line = capitalise(&data["faction_code"].as_str().unwrap().replace('-', " "));
+    if let Some(influence) = data["faction_cost"].as_i64() {
+        faction_line.push_str(&format!(" - influence {}", influence));
+    }
+    if let Some(limit) = data["influence_limit"].as_i64() {
+        faction_line.push_str(&format!(" - influence limit {}", limit));
+    }
+
+    let mut magick = tokio::process::Command::new("magick");
+    magick
+        .arg("-size")
+        .arg("744x1031")
+        .arg("xc:white")
+        .arg("-fill")
+        .arg("none")
+        .arg("-stroke")
+        .arg(colour)
+        .arg("-strokewidth")
+        .arg("24")
+        .arg("-draw")
+        .arg("rectangle 12,12 731,1018")
+        .arg("-stroke")
+        .arg("none")
+        .arg("-gravity")
+        .arg("northwest");
+    let blocks = [
+        (&title, "black", 44, 120, 40),
+        (&type_line, colour, 30, 50, 160),
+        (&card_stat_line(data), "black", 30, 50, 215),
+        (&card_text(data), "black", 28, 660, 280),
+        (&faction_line, colour, 26, 40, 950),
+    ];
+    for (text, fill, pointsize, height, top) in blocks {
+        magick
+            .arg("(")
+            .arg("-size")
+            .arg(format!("664x{}", height))
+            .arg("-background")
+            .arg("none")
+            .arg("-fill")
+            .arg(fill)
+            .arg("-pointsize")
+            .arg(pointsize.to_string())
+            .arg(format!("caption:{}", escape_magick_text(text)))
+            .arg(")")
+            .arg("-geometry")
+            .arg(format!("+40+{}", top))
+            .arg("-composite");
+    }
+    magick
+        .arg(path.join(&image))
+        .spawn()
+        .unwrap()
+        .wait()
+        .await
+        .unwrap();
+    image
+}
+
+/// The numbers printed on a card, depending on what type of card it is.
+fn card_stat_line(data: &serde_json::Value) -> String {
+    let stats = [
+        ("cost", "Cost"),
+        ("strength", "Strength"),
+        ("memory_cost", "MU"),
+        ("trash_cost", "Trash"),
+        ("advancement_cost", "Advancement"),
+        ("agenda_points", "Agenda points"),
+        ("base_link", "Link"),
+        ("minimum_deck_size", "Deck size"),
+    ];
+    stats
+        .iter()
+        .filter_map(|(field, name)| data[field].as_i64().map(|n| format!("{} {}", name, n)))
+        .collect::<Vec<_>>()
+        .join(" - ")
+}
+
+/// The card text from NRDB with markup stripped and symbols spelled out.
+fn card_text(data: &serde_json::Value) -> String {
+    let symbols = [
+        ("[click]", "[Click]"),
+        ("[credit]", "credits"),
+        ("[recurring-credit]", "recurring credits"),
+        ("[subroutine]", "->"),
+        ("[trash]", "[Trash]"),
+        ("[link]", "link"),
+        ("[mu]", "MU"),
+        ("[interrupt]", "Interrupt ->"),
+    ];
+    let mut text = data["text"].as_str().unwrap_or_default().to_string();
+    for (symbol, replacement) in symbols {
+        text = text.replace(symbol, replacement);
+    }
+    let mut stripped = String::new();
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => stripped.push(c),
+            _ => {}
+        }
+    }
+    stripped
+}
+
+fn faction_colour(faction: &str) -> &'static str {
+    match faction {
+        "anarch" => "#e8590c",
+        "criminal" => "#1c7ed6",
+        "shaper" => "#37b24d",
+        "haas-bioroid" => "#7048e8",
+        "jinteki" => "#c92a2a",
+        "nbn" => "#f59f00",
+        "weyland-consortium" => "#1d5e3a",
+        _ => "#868e96",
+    }
+}
+
+fn capitalise(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// ImageMagick treats `%`, `\` and a leading `@` specially in caption text.
+fn escape_magick_text(text: &str) -> String {
+    let text = text.replace('\\', "\\\\").replace('%', "%%");
+    match text.strip_prefix('@') {
+        Some(text) => format!("\\@{}", text),
+        None => text,
+    }
+}
+
+fn report_unresolved(unresolved: &[Card]) {
+    if unresolved.is_empty() {
+        return;
+    }
+    println!(
+        "{} cards could not be proxied from a PNP set:",
+        unresolved.len()
+    );
+    for card in unresolved {
+        if card.data.is_null() {
+            println!("  {}: not on NetrunnerDB, skipped", card.code);
+            continue;
+        }
+        let action = match (card.fallback, &card.image) {
+            (_, None) => "skipped",
+            (Some(Fallback::Nrdb), _) => "downloaded from NetrunnerDB",
+            (Some(Fallback::Text), _) => "text proxy printed",
+            (Some(Fallback::Placeholder), _) => "placeholder printed",
+            (Some(Fallback::Skip) | None, _) => "skipped",
+        };
+        println!(
+            "  {} {} ({}): {}",
+            card.code,
+            card.data["title"].as_str().unwrap(),
+            card.data["pack_code"].as_str().unwrap(),
+            action
+        );
+    }
+}
+
+/// Reads a collection of owned cards, in the same shapes as a local deck file.
+async fn load_collection(file: &Path) -> HashMap<String, i64> {
+    let read = fs::read(file).await.unwrap();
+    let collection = parse_deck("collection", &serde_json::from_slice(&read).unwrap());
+    collection.cards.into_iter().collect()
+}
+
+/// The ledger of proxies already printed on earlier runs, by card code.
+async fn load_ledger(path: &Path) -> HashMap<String, i64> {
+    match fs::read(path.join("ledger.json")).await {
+        Ok(read) => serde_json::from_slice(&read).unwrap(),
+        Err(_) => HashMap::new(),
+    }
+}
+
+async fn save_ledger(path: &Path, ledger: &HashMap<String, i64>) {
+    let ledger: serde_json::Map<String, serde_json::Value> = ledger
+        .iter()
+        .filter(|(_, count)| **count > 0)
+        .map(|(code, count)| (code.clone(), (*count).into()))
+        .collect();
+    fs::write(
+        path.join("ledger.json"),
+        serde_json::to_string_pretty(&ledger).unwrap(),
+    )
+    .await
+    .unwrap();
+}
+
+/// Resets, adjusts and then shows the printed-cards ledger, as requested.
+async fn update_ledger(opt: &Opt) {
+    let path = opt.card_dir.as_path();
+    let mut ledger = if opt.ledger_reset {
+        HashMap::new()
+    } else {
+        load_ledger(path).await
+    };
+    for adjust in opt.ledger_adjust.iter() {
+        let (code, delta) = adjust
+            .split_once('=')
+            .expect("ledger adjustments look like <code>=<change>");
+        let count = ledger.entry(code.to_string()).or_default();
+        *count = (*count + delta.parse::<i64>().unwrap()).max(0);
+    }
+    save_ledger(path, &ledger).await;
+    if opt.ledger_show {
+        let mut ledger: Vec<_> = ledger.into_iter().filter(|(_, count)| *count > 0).collect();
+        ledger.sort();
+        println!("Printed proxies on the shelf:");
+        for (code, count) in ledger {
+            let card = get_card(path, &code).await;
+            println!("  {}x {} {}", count, code, card["title"].as_str().unwrap());
+        }
+    }
+}
+
+/// Uses up owned copies of a card, returning how many still need printing.
+fn take_owned(
+    owned: &mut HashMap<String, i64>,
+    card: &Card,
+    count: i64,
+    skipped: &mut Vec<(Card, i64)>,
+) -> i64 {
+    let Some(have) = owned.get_mut(&card.code) else {
+        return count;
+    };
+    let used = count.min(*have);
+    *have -= used;
+    if used > 0 {
+        skipped.push((card.clone(), used));
+    }
+    count - used
+}
+
+fn report_skipped(skipped: &[(Card, i64)], reason: &str) {
+    if skipped.is_empty() {
+        return;
+    }
+    let total: i64 = skipped.iter().map(|(_, count)| count).sum();
+    println!("{} cards {}, not printed:", total, reason);
+    for (card, count) in skipped {
+        println!(
+            "  {}x {} {}",
+            count,
+            card.code,
+            card.data["title"].as_str().unwrap()
+        );
+    }
+}
+
+async fn get_json_cached(path: &Path, url: &str) -> serde_json::Value {
+    let hash = md5::compute(url);
+    let store = format!("{:?}.json", hash);
+    let store = path.join(store);
+    create_dir_all(path).unwrap();
+    if let Ok(read) = fs::read(&store).await {
+        return serde_json::from_slice(&read).unwrap();
+    }
+    let json: serde_json::Value = reqwest::get(url).await.unwrap().json().await.unwrap();
+    fs::write(&store, json.to_string()).await.unwrap();
+    json
+}
+
+/// Drops the cached response for `url` and fetches it again.
+async fn get_json_refreshed(path: &Path, url: &str) -> serde_json::Value {
+    let hash = md5::compute(url);
+    let store = path.join(format!("{:?}.json", hash));
+    if matches!(fs::try_exists(&store).await, Ok(true)) {
+        fs::remove_file(&store).await.unwrap();
+    }
+    get_json_cached(path, url).await
+}
+
+async fn acquire_card_back(path: &Path, back: &str) {
+    let download_path = download_back_raw(back, path).await;
+    let to = path.join("back.png");
+    crop_raw(&download_path, &to, 744, 1031, 40, 47).await;
+}
+
+/// Takes the corp and runner card backs from the back pages of the printable
+/// sheets, for any side that doesn't have a back yet.
+///
+/// Which side a back page is for is told apart by colour, corp backs being
+/// mostly blue and runner backs mostly red. Pages that are neither, such as
+/// blank backs of rules pages, are ignored. Each page is only checked once,
+/// delete `back/extract` to check them all again.
+async fn extract_card_backs(path: &Path, sets: &[&str]) {
+    let back = path.join("back");
+    'sets: for set in sets {
+        let manifest: serde_json::Value = serde_json::from_slice(
+            &fs::read(path.join(set).join("manifest.json"))
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        let scale = manifest["scale"].as_u64().unwrap_or(1) as u32;
+        for page in manifest["backs"].as_array().unwrap() {
+            let mut found = 0;
+            for side in ["corp", "runner"] {
+                if matches!(
+                    fs::try_exists(back.join(side).join("back.png")).await,
+                    Ok(true)
+                ) {
+                    found += 1;
+                }
+            }
+            if found == 2 {
+                break 'sets;
+            }
+            let page_name = page["page"].as_str().unwrap();
+            let cell = back
+                .join("extract")
+                .join(format!("{}-{}.png", set, page_name));
+            // Pages are only checked once, the cell is kept as a record of it
+            if matches!(fs::try_exists(&cell).await, Ok(true)) {
+                continue;
+            }
+            fs::create_dir_all(cell.parent().unwrap()).await.unwrap();
+            let page = path.join(set).join(page["image"].as_str().unwrap());
+            crop_raw(
+                &page,
+                &cell,
+                744 * scale,
+                1031 * scale,
+                76 * scale,
+                76 * scale,
+            )
+            .await;
+            let [red, green, blue] = mean_colour(&cell).await;
+            let side = if blue > red + 0.1 && blue > green {
+                "corp"
+            } else if red > blue + 0.1 && red > green {
+                "runner"
+            } else {
+                continue;
+            };
+            let to = back.join(side).join("back.png");
+            if !matches!(fs::try_exists(&to).await, Ok(true)) {
+                println!("{} back taken from {} {}", side, set, page_name);
+                fs::create_dir_all(to.parent().unwrap()).await.unwrap();
+                fs::copy(&cell, &to).await.unwrap();
+            }
+        }
+    }
+    for side in ["corp", "runner"] {
+        if !matches!(
+            fs::try_exists(back.join(side).join("back.png")).await,
+            Ok(true)
+        ) {
+            println!(
+                "No {} card back found in the printable sheets, pass --{}-back with an image URL",
+                side, side
+            );
+        }
+    }
+}
+
+/// Validates the custom card images in the overrides dir and resizes them to
+/// the card size into `overrides/cut`, where they take precedence over the
+/// PNP cuts.
+///
+/// Images are named by card code (`overrides/<code>.png`) or by pack and
+/// position like the cuts (`overrides/<pack>/c-NNN.png`), and are imported
+/// again whenever they change.
+async fn import_overrides(path: &Path) {
+    if !matches!(fs::try_exists(path).await, Ok(true)) {
+        return;
+    }
+    let mut images = Vec::new();
+    let mut entries = fs::read_dir(path).await.unwrap();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        if entry.file_name() == "cut" {
+            continue;
+        }
+        if !entry.file_type().await.unwrap().is_dir() {
+            images.push(PathBuf::from(entry.file_name()));
+            continue;
+        }
+        let mut pack = fs::read_dir(entry.path()).await.unwrap();
+        while let Ok(Some(image)) = pack.next_entry().await {
+            images.push(PathBuf::from(entry.file_name()).join(image.file_name()));
+        }
+    }
+    for image in images {
+        let from = path.join(&image);
+        let to = path.join("cut").join(&image).with_extension("png");
+        if let (Ok(from), Ok(to)) = (fs::metadata(&from).await, fs::metadata(&to).await) {
+            if to.modified().unwrap() >= from.modified().unwrap() {
+                continue;
+            }
+        }
+        let identify = tokio::process::Command::new("magick")
+            .arg("identify")
+            .arg("-format")
+            .arg("%w %h")
+            .arg(&from)
+            .output()
+            .await
+            .unwrap();
+        if !identify.status.success() {
+            println!("{:?} is not a valid image, ignoring override", from);
+            continue;
         }
+        let size = String::from_utf8(identify.stdout).unwrap();
+        let mut size = size.split_whitespace().map(|n| n.parse::<f64>().unwrap());
+        let (width, height) = (size.next().unwrap(), size.next().unwrap());
+        if ((width / height) / (744.0 / 1031.0) - 1.0).abs() > 0.05 {
+            println!(
+                "{:?} is not card shaped ({}x{}), it will be stretched",
+                from, width, height
+            );
+        }
+        fs::create_dir_all(to.parent().unwrap()).await.unwrap();
+        resize_raw(&from, &to, 744, 1031).await;
+        println!("{:?} override imported", from);
+    }
+}
 
-        let path = path.join("tts").join(&deck);
-        tokio::fs::create_dir_all(&path).await.unwrap();
+/// The sets acquired from NSG's printable sheets, by NRDB pack code.
+const PNP_SETS: [&str; 6] = ["sg", "su21", "tai", "rwr", "ms", "ph"];
 
-        let mut last_row: Option<PathBuf> = None;
-        'rows: for (col, paths) in grid.into_iter().enumerate() {
-            let row = path.join(format!("part-{col:>02}-XX.png"));
-            if matches!(fs::try_exists(&row).await, Ok(true)) {
-                println!("{:?} tts row already generated, skipping", row);
-                last_row = Some(row.clone());
-                continue 'rows;
-            }
-            let mut last_out: Option<PathBuf> = None;
-            'outs: for (row, card) in paths.into_iter().enumerate() {
-                let out = path.join(format!("part-{col:>02}-{row:>02}.png"));
-                if matches!(fs::try_exists(&out).await, Ok(true)) {
-                    println!("{:?} tts out already generated, skipping", out);
-                    last_out = Some(out.to_owned());
-                    continue 'outs;
-                }
-                if row == 0 {
-                    tokio::fs::copy(&card, &out).await.unwrap();
-                } else {
-                    merge_raw(&last_out.unwrap(), &card, &out, true).await;
-                }
-                last_out = Some(out.to_owned());
+/// A set's table of cuts that don't match their NRDB position, from
+/// `<set>/positions.json` which maps NRDB positions to cut positions.
+async fn load_positions(path: &Path, set: &str) -> HashMap<i64, i64> {
+    let read = match fs::read(path.join(set).join("positions.json")).await {
+        Ok(read) => read,
+        Err(_) => return HashMap::new(),
+    };
+    let positions: HashMap<String, i64> = serde_json::from_slice(&read).unwrap();
+    positions
+        .into_iter()
+        .map(|(position, cut)| (position.parse().unwrap(), cut))
+        .collect()
+}
+
+/// Checks that each set's cuts show the card NRDB has at that position, by
+/// comparing them to the card images on NetrunnerDB.
+///
+/// Mismatches are reported, and a corrected table is written to
+/// `<set>/positions.suggested.json` to be renamed to `positions.json` once
+/// checked.
+async fn verify_positions(path: &Path, cards: &HashMap<(String, i64), serde_json::Value>) {
+    for set in PNP_SETS {
+        let positions = load_positions(path, set).await;
+        let mut cuts = Vec::new();
+        let mut entries = fs::read_dir(path.join(set).join("cut")).await.unwrap();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let file_name = entry.file_name().into_string().unwrap();
+            let Some(position) = cut_position(&file_name) else {
+                continue;
+            };
+            cuts.push((position, fingerprint(&entry.path()).await));
+        }
+        if cuts.is_empty() {
+            println!("{} has no cuts to check", set);
+            continue;
+        }
+
+        let mut set_cards: Vec<_> = cards.iter().filter(|((pack, _), _)| pack == set).collect();
+        set_cards.sort_by_key(|((_, position), _)| *position);
+        let mut suggested = serde_json::Map::new();
+        let mut mismatches = 0;
+        for ((_, position), card) in set_cards {
+            let Some(image) = download_nrdb_image(path, card).await else {
+                continue;
+            };
+            let expected = fingerprint(&image).await;
+            let mapped = positions.get(position).copied().unwrap_or(*position);
+            let distance = |cut: &[u8]| fingerprint_distance(cut, &expected);
+            let (best, best_distance) = cuts
+                .iter()
+                .map(|(cut, fingerprint)| (*cut, distance(fingerprint)))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap();
+            let mapped_distance = cuts
+                .iter()
+                .find(|(cut, _)| *cut == mapped)
+                .map(|(_, fingerprint)| distance(fingerprint));
+            let matches = match mapped_distance {
+                Some(mapped_distance) => mapped_distance <= best_distance + 5.0,
+                None => false,
+            };
+            let cut = if matches { mapped } else { best };
+            if cut != *position {
+                suggested.insert(position.to_string(), cut.into());
             }
-            if col == 0 {
-                tokio::fs::copy(dbg!(&last_out.unwrap()), dbg!(&row))
-                    .await
-                    .unwrap();
-            } else {
-                merge_raw(&last_row.unwrap(), &last_out.unwrap(), &row, false).await;
+            if !matches {
+                mismatches += 1;
+                println!(
+                    "{} {} ({}) is cut as c-{:>03}.png, but looks like c-{:>03}.png",
+                    set,
+                    position,
+                    card["title"].as_str().unwrap(),
+                    mapped,
+                    best
+                );
             }
-            last_row = Some(row.clone())
         }
-        tokio::fs::copy(&last_row.unwrap(), path.join(format!("_-{deck}.png")))
-            .await
-            .unwrap();
+        println!("{} positions checked, {} mismatches", set, mismatches);
+        fs::write(
+            path.join(set).join("positions.suggested.json"),
+            serde_json::to_string_pretty(&suggested).unwrap(),
+        )
+        .await
+        .unwrap();
     }
 }
 
-async fn build_documents(
+/// The packs in the card dir that have cuts.
+async fn cut_sets(path: &Path) -> Vec<String> {
+    let mut entries = fs::read_dir(path).await.unwrap();
+    let mut sets = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let set = entry.file_name().into_string().unwrap();
+        if set != "overrides" && matches!(fs::try_exists(entry.path().join("cut")).await, Ok(true))
+        {
+            sets.push(set);
+        }
+    }
+    sets.sort();
+    sets
+}
+
+/// Compares each pack's cuts with the pack on NetrunnerDB, listing positions
+/// that are missing, extra or doubled up (several positions showing the same
+/// image).
+async fn sets_report(
     path: &Path,
-    decks: Vec<String>,
-    include_basic_actions: bool,
-    include_marks: bool,
-    skip_ids: bool,
+    cards: &HashMap<(String, i64), serde_json::Value>,
+    sets: &[String],
 ) {
-    let mut document = String::new();
-    document.push_str("<!DOCTYPE html>\n");
-    document.push_str("<html lang=\"en\"><head><meta charset=\"UTF-8\"><meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\"><title>PDF</title><style>*,::after,::before{margin:0;padding:0;min-width:0}.page{width:210mm;height:297mm;display:grid;place-items:center}.imgs{display:grid;grid-template-columns:63mm 63mm 63mm;grid-template-rows:88mm 88mm 88mm;gap:0.5pt}img{width:100%;height:100%}</style></head><body><div class=\"page\"><div class=\"imgs\">");
-    let mut index = 0;
-    for deck in decks.iter() {
-        let deck_data = get_json_cached(
-            &path.join("cache"),
-            &format!("https://netrunnerdb.com/api/2.0/public/deck/{}", deck),
-        )
-        .await;
-        let cards = &deck_data["data"][0]["cards"].as_object().unwrap();
-        for (card, count) in cards.iter() {
-            let count = count.as_number().unwrap().as_i64().unwrap();
-            let card_data = get_json_cached(
-                &path.join("cache"),
-                &format!("https://netrunnerdb.com/api/2.0/public/card/{}", card),
-            )
-            .await;
-            let card_pack = &card_data["data"][0]["pack_code"].as_str().unwrap();
-            let card_position = card_data["data"][0]["position"]
-                .as_number()
-                .unwrap()
-                .as_i64()
-                .unwrap();
-            let card_type_code = card_data["data"][0]["type_code"].as_str().unwrap();
-            if card_type_code == "identity" && skip_ids {
-                continue;
+    let packs = get_json_cached(
+        &path.join("cache"),
+        "https://netrunnerdb.com/api/2.0/public/packs",
+    )
+    .await;
+    for set in sets {
+        let positions = load_positions(path, set).await;
+        let mut cuts = HashMap::new();
+        let mut entries = fs::read_dir(path.join(set).join("cut")).await.unwrap();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let file_name = entry.file_name().into_string().unwrap();
+            if let Some(position) = cut_position(&file_name) {
+                let hash = md5::compute(fs::read(entry.path()).await.unwrap());
+                cuts.insert(position, hash);
             }
-            for _ in 0..count {
-                document.push_str(&format!(
-                    "<img src=\"{}/cut/c-{:>03}.png\" />",
-                    card_pack, card_position
-                ));
-                if index % 9 == 8 {
-                    document.push_str("</div></div><div class=\"page\"><div class=\"imgs\">");
-                }
-                index += 1;
+        }
+        let mut expected: Vec<i64> = cards
+            .keys()
+            .filter(|(pack, _)| pack == set)
+            .map(|(_, position)| *position)
+            .collect();
+        expected.sort();
+        let cut_for = |position: i64| positions.get(&position).copied().unwrap_or(position);
+
+        let missing: Vec<i64> = expected
+            .iter()
+            .copied()
+            .filter(|position| !cuts.contains_key(&cut_for(*position)))
+            .collect();
+        let used: Vec<i64> = expected.iter().map(|position| cut_for(*position)).collect();
+        let mut extra: Vec<i64> = cuts
+            .keys()
+            .copied()
+            .filter(|cut| !used.contains(cut))
+            .collect();
+        extra.sort();
+        let mut doubled: Vec<i64> = expected
+            .iter()
+            .copied()
+            .filter(|position| {
+                let Some(hash) = cuts.get(&cut_for(*position)) else {
+                    return false;
+                };
+                expected
+                    .iter()
+                    .any(|other| other != position && cuts.get(&cut_for(*other)) == Some(hash))
+            })
+            .collect();
+        doubled.sort();
+
+        let size = packs["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|pack| pack["code"] == set.as_str())
+            .and_then(|pack| pack["size"].as_i64());
+        println!(
+            "{}: {} cuts, NetrunnerDB has {} cards (pack size {})",
+            set,
+            cuts.len(),
+            expected.len(),
+            size.map_or("unknown".to_string(), |size| size.to_string())
+        );
+        for (problem, positions) in [("missing", missing), ("extra", extra), ("doubled", doubled)] {
+            if !positions.is_empty() {
+                println!("  {}: {}", problem, format_positions(&positions));
             }
         }
     }
-    if include_basic_actions {
-        for card_position in 78..=79 {
-            document.push_str(&format!(
-                "<img src=\"sg/cut/c-{:>03}.png\" />",
-                card_position
-            ));
-            if index % 9 == 8 {
-                document.push_str("</div></div><div class=\"page\"><div class=\"imgs\">");
-            }
-            index += 1;
+}
+
+/// Sorted positions as a compact list of ranges, like `1-3, 7, 9-10`.
+fn format_positions(positions: &[i64]) -> String {
+    let mut ranges: Vec<(i64, i64)> = Vec::new();
+    for position in positions {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == *position => *end = *position,
+            _ => ranges.push((*position, *position)),
         }
     }
-    if include_marks {
-        for card_position in 66..=68 {
-            document.push_str(&format!(
-                "<img src=\"ms/cut/c-{:>03}.png\" />",
-                card_position
-            ));
-            if index % 9 == 8 {
-                document.push_str("</div></div><div class=\"page\"><div class=\"imgs\">");
+    ranges
+        .iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
             }
-            index += 1;
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Writes `<set>/contact-sheet.png`, a grid of thumbnails of every cut in the
+/// set labelled with its position and NRDB title, to check the cuts at a
+/// glance.
+async fn build_contact_sheet(
+    path: &Path,
+    cards: &HashMap<(String, i64), serde_json::Value>,
+    set: &str,
+) {
+    let mut remapped = HashMap::new();
+    for (position, cut) in load_positions(path, set).await {
+        remapped.insert(cut, position);
+    }
+    let mut cuts = Vec::new();
+    let mut entries = fs::read_dir(path.join(set).join("cut")).await.unwrap();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let file_name = entry.file_name().into_string().unwrap();
+        if let Some(position) = cut_position(&file_name) {
+            cuts.push((position, entry.path()));
         }
     }
-    document.push_str("</body></html>");
+    cuts.sort();
 
-    let name = decks.join("_");
-    fs::write(path.join(format!("{}.html", name)), document)
+    let mut montage = tokio::process::Command::new("magick");
+    montage.arg("montage");
+    for (cut_position, cut) in cuts {
+        let position = remapped.get(&cut_position).copied().unwrap_or(cut_position);
+        let title = match cards.get(&(set.to_string(), position)) {
+            Some(card) => card["title"].as_str().unwrap(),
+            None => "not on NetrunnerDB",
+        };
+        montage
+            .arg("-label")
+            .arg(escape_magick_text(&format!(
+                "{:>03} {}",
+                cut_position, title
+            )))
+            .arg(cut);
+    }
+    let to = path.join(set).join("contact-sheet.png");
+    montage
+        .arg("-tile")
+        .arg("9x")
+        .arg("-geometry")
+        .arg("186x258+6+6")
+        .arg("-pointsize")
+        .arg("12")
+        .arg(&to)
+        .spawn()
+        .unwrap()
+        .wait()
         .await
         .unwrap();
+    println!("{:?} contact sheet generated", to);
 }
 
-async fn get_json_cached(path: &Path, url: &str) -> serde_json::Value {
-    let hash = md5::compute(url);
-    let store = format!("{:?}.json", hash);
-    let store = path.join(store);
-    create_dir_all(path).unwrap();
-    if let Ok(read) = fs::read(&store).await {
-        return serde_json::from_slice(&read).unwrap();
+/// A tiny greyscale thumbnail of an image, to compare images by.
+async fn fingerprint(image: &Path) -> Vec<u8> {
+    tokio::process::Command::new("magick")
+        .arg(image)
+        .arg("-resize")
+        .arg("16x16!")
+        .arg("-colorspace")
+        .arg("Gray")
+        .arg("-depth")
+        .arg("8")
+        .arg("gray:-")
+        .output()
+        .await
+        .unwrap()
+        .stdout
+}
+
+/// The mean difference between two fingerprints, from 0 to 255.
+fn fingerprint_distance(a: &[u8], b: &[u8]) -> f64 {
+    let total: u32 = a.iter().zip(b).map(|(a, b)| a.abs_diff(*b) as u32).sum();
+    total as f64 / a.len().max(1) as f64
+}
+
+/// All NRDB cards, keyed by pack code and position.
+async fn cards_by_position(path: &Path) -> HashMap<(String, i64), serde_json::Value> {
+    let cards = get_json_cached(
+        &path.join("cache"),
+        "https://netrunnerdb.com/api/2.0/public/cards",
+    )
+    .await;
+    cards["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|card| {
+            let pack = card["pack_code"].as_str().unwrap().to_string();
+            ((pack, card["position"].as_i64().unwrap()), card.clone())
+        })
+        .collect()
+}
+
+/// Writes `index.json` in the card dir, listing every card image available
+/// with its NRDB code, title, pack, position, path, size and MD5 hash, so
+/// other tools can look images up by card code. The file size and
+/// modification time are recorded too, to skip unchanged images next time.
+async fn build_index(path: &Path, cards: &HashMap<(String, i64), serde_json::Value>) {
+    // Images with the same size and modification time as in the last index
+    // aren't read and hashed again
+    let previous: serde_json::Value = match fs::read(path.join("index.json")).await {
+        Ok(read) => serde_json::from_slice(&read).unwrap_or_default(),
+        Err(_) => serde_json::Value::Null,
+    };
+    let previous: HashMap<&str, &serde_json::Value> = previous["cards"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|card| Some((card["path"].as_str()?, card)))
+        .collect();
+    let by_code: HashMap<&str, &serde_json::Value> = cards
+        .values()
+        .map(|card| (card["code"].as_str().unwrap(), card))
+        .collect();
+
+    // (pack, cut dir relative to the card dir, source)
+    let mut dirs = Vec::new();
+    let mut entries = fs::read_dir(path).await.unwrap();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let pack = entry.file_name().into_string().unwrap();
+        if matches!(fs::try_exists(entry.path().join("cut")).await, Ok(true)) && pack != "overrides"
+        {
+            dirs.push((Some(pack.clone()), PathBuf::from(&pack).join("cut"), "cut"));
+        }
     }
-    let json: serde_json::Value = reqwest::get(url).await.unwrap().json().await.unwrap();
-    fs::write(&store, json.to_string()).await.unwrap();
-    json
+    let overrides = PathBuf::from("overrides").join("cut");
+    if matches!(fs::try_exists(path.join(&overrides)).await, Ok(true)) {
+        dirs.push((None, overrides.clone(), "override"));
+        let mut entries = fs::read_dir(path.join(&overrides)).await.unwrap();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if entry.file_type().await.unwrap().is_dir() {
+                let pack = entry.file_name().into_string().unwrap();
+                dirs.push((Some(pack.clone()), overrides.join(&pack), "override"));
+            }
+        }
+    }
+
+    let mut index = Vec::new();
+    for (pack, dir, source) in dirs {
+        // Cuts named after a different position than NRDB's
+        let mut remapped = HashMap::new();
+        if let (Some(pack), "cut") = (&pack, source) {
+            for (position, cut) in load_positions(path, pack).await {
+                remapped.insert(cut, position);
+            }
+        }
+        let mut images = fs::read_dir(path.join(&dir)).await.unwrap();
+        while let Ok(Some(image)) = images.next_entry().await {
+            let file_name = image.file_name().into_string().unwrap();
+            let Some(stem) = file_name.strip_suffix(".png") else {
+                continue;
+            };
+            let card = match (&pack, cut_position(&file_name)) {
+                (Some(pack), Some(position)) => {
+                    let position = remapped.get(&position).copied().unwrap_or(position);
+                    cards.get(&(pack.clone(), position))
+                }
+                _ => by_code.get(stem).copied(),
+            };
+            let metadata = image.metadata().await.unwrap();
+            let bytes = metadata.len();
+            let modified = metadata
+                .modified()
+                .unwrap()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64;
+            let image_path = dir.join(&file_name);
+            let (width, height, md5) = match previous.get(image_path.to_str().unwrap()) {
+                Some(last) if last["bytes"] == bytes && last["modified"] == modified => (
+                    last["width"].as_u64().unwrap_or_default() as u32,
+                    last["height"].as_u64().unwrap_or_default() as u32,
+                    last["md5"].as_str().unwrap_or_default().to_string(),
+                ),
+                _ => {
+                    let read = fs::read(image.path()).await.unwrap();
+                    let (width, height) = png_size(&read).unwrap_or_default();
+                    (width, height, format!("{:x}", md5::compute(&read)))
+                }
+            };
+            index.push(serde_json::json!({
+                "code": card.map(|card| &card["code"]),
+                "title": card.map(|card| &card["title"]),
+                "pack": card.map(|card| &card["pack_code"]),
+                "position": card.map(|card| &card["position"]),
+                "path": image_path,
+                "source": source,
+                "width": width,
+                "height": height,
+                "md5": md5,
+                "bytes": bytes,
+                "modified": modified,
+            }));
+        }
+    }
+    index.sort_by(|a, b| a["path"].as_str().cmp(&b["path"].as_str()));
+    fs::write(
+        path.join("index.json"),
+        serde_json::to_string_pretty(&serde_json::json!({ "cards": index })).unwrap(),
+    )
+    .await
+    .unwrap();
 }
 
-async fn acquire_card_back(path: &Path, back: &str) {
-    let download_path = download_back_raw(back, &path).await;
-    let to = path.join("back.png");
-    crop_raw(&download_path, &to, 744, 1031, 40, 47).await;
+/// The position in a cut's file name, `c-NNN.png`.
+fn cut_position(file_name: &str) -> Option<i64> {
+    file_name
+        .strip_prefix("c-")
+        .and_then(|name| name.strip_suffix(".png"))
+        .and_then(|position| position.parse().ok())
+}
+
+/// Width and height from a PNG's header.
+fn png_size(png: &[u8]) -> Option<(u32, u32)> {
+    if png.len() < 24 || &png[..8] != b"\x89PNG\r\n\x1a\n" {
+        return None;
+    }
+    let width = u32::from_be_bytes(png[16..20].try_into().unwrap());
+    let height = u32::from_be_bytes(png[20..24].try_into().unwrap());
+    Some((width, height))
 }
 
 async fn acquire_system_gateway_set(path: &Path, sg: &str) {
     let download_path = download_set_pdf(sg, &path.join("download")).await;
+    let scale = sheet_scale(&download_path).await;
     let mut extracted = extract_images(&download_path, &path.join("extract")).await;
     let mut good_images = Vec::new();
+    let mut extras = Vec::new();
+    let mut backs = Vec::new();
     'entries: while let Ok(Some(entry)) = extracted.next_entry().await {
         let file_name = entry.file_name().into_string().unwrap();
         if file_name.starts_with("x-000") {
+            extras.push(entry.path());
             continue;
         }
         if file_name.starts_with("x-020") {
+            extras.push(entry.path());
             continue;
         }
         if file_name.starts_with("x-022") {
+            extras.push(entry.path());
             continue;
         }
         for n in 1..=23 {
             if n % 2 != 0 && file_name.starts_with(&format!("x-{:>03}", n)) {
+                backs.push(entry.path());
                 continue 'entries;
             }
         }
         good_images.push(entry.path());
     }
+    catalogue_pages(path, extras, backs, scale).await;
     let good_images = good_images.iter().map(|n| n.as_path());
-    let mut cropped = crop_images(good_images, &path.join("crop"), 2233, 3093, 76, 76).await;
+    let mut cropped = crop_images(good_images, &path.join("crop"), 2233, 3093, 76, 76, scale).await;
     let mut cropped_images = Vec::new();
     while let Ok(Some(entry)) = cropped.next_entry().await {
         cropped_images.push(entry.path())
@@ -324,28 +2462,35 @@ async fn acquire_system_gateway_set(path: &Path, sg: &str) {
             [744, 1031, 1489, 2062],
         ],
         0,
+        scale,
     )
     .await;
 }
 
 async fn acquire_system_update_set(path: &Path, su: &str) {
     let download_path = download_set_pdf(su, &path.join("download")).await;
+    let scale = sheet_scale(&download_path).await;
     let mut extracted = extract_images(&download_path, &path.join("extract")).await;
     let mut good_images = Vec::new();
+    let mut extras = Vec::new();
+    let mut backs = Vec::new();
     'entries: while let Ok(Some(entry)) = extracted.next_entry().await {
         let file_name = entry.file_name().into_string().unwrap();
         if file_name.starts_with("x-000") {
+            extras.push(entry.path());
             continue;
         }
         for n in 1..=21 {
             if n % 2 != 0 && file_name.starts_with(&format!("x-{:>03}", n)) {
+                backs.push(entry.path());
                 continue 'entries;
             }
         }
         good_images.push(entry.path());
     }
+    catalogue_pages(path, extras, backs, scale).await;
     let good_images = good_images.iter().map(|n| n.as_path());
-    let mut cropped = crop_images(good_images, &path.join("crop"), 2233, 3093, 76, 76).await;
+    let mut cropped = crop_images(good_images, &path.join("crop"), 2233, 3093, 76, 76, scale).await;
     let mut cropped_images = Vec::new();
     while let Ok(Some(entry)) = cropped.next_entry().await {
         cropped_images.push(entry.path())
@@ -366,34 +2511,43 @@ async fn acquire_system_update_set(path: &Path, su: &str) {
             [744, 1031, 1489, 2062],
         ],
         0,
+        scale,
     )
     .await;
 }
 
 async fn acquire_the_automata_initiative_set(path: &Path, tai: &str) {
     let download_path = download_set_pdf(tai, &path.join("download")).await;
+    let scale = sheet_scale(&download_path).await;
     let mut extracted = extract_images(&download_path, &path.join("extract")).await;
     let mut good_images = Vec::new();
+    let mut extras = Vec::new();
+    let mut backs = Vec::new();
     'entries: while let Ok(Some(entry)) = extracted.next_entry().await {
         let file_name = entry.file_name().into_string().unwrap();
         if file_name.starts_with("x-000") {
+            extras.push(entry.path());
             continue;
         }
         if file_name.starts_with("x-018") {
+            extras.push(entry.path());
             continue;
         }
         if file_name.starts_with("x-020") {
+            extras.push(entry.path());
             continue;
         }
         for n in 1..=21 {
             if n % 2 != 0 && file_name.starts_with(&format!("x-{:>03}", n)) {
+                backs.push(entry.path());
                 continue 'entries;
             }
         }
         good_images.push(entry.path());
     }
+    catalogue_pages(path, extras, backs, scale).await;
     let good_images = good_images.iter().map(|n| n.as_path());
-    let mut cropped = crop_images(good_images, &path.join("crop"), 2233, 3093, 76, 76).await;
+    let mut cropped = crop_images(good_images, &path.join("crop"), 2233, 3093, 76, 76, scale).await;
     let mut cropped_images = Vec::new();
     while let Ok(Some(entry)) = cropped.next_entry().await {
         cropped_images.push(entry.path())
@@ -414,28 +2568,35 @@ async fn acquire_the_automata_initiative_set(path: &Path, tai: &str) {
             [744, 1031, 1489, 2062],
         ],
         0,
+        scale,
     )
     .await;
 }
 
 async fn acquire_rebellion_without_reversal_set(path: &Path, rwr: &str) {
     let download_path = download_set_pdf(rwr, &path.join("download")).await;
+    let scale = sheet_scale(&download_path).await;
     let mut extracted = extract_images(&download_path, &path.join("extract")).await;
     let mut good_images = Vec::new();
+    let mut extras = Vec::new();
+    let mut backs = Vec::new();
     'entries: while let Ok(Some(entry)) = extracted.next_entry().await {
         let file_name = entry.file_name().into_string().unwrap();
         if file_name.starts_with("x-000") {
+            extras.push(entry.path());
             continue;
         }
         for n in 1..=17 {
             if n % 2 != 0 && file_name.starts_with(&format!("x-{:>03}", n)) {
+                backs.push(entry.path());
                 continue 'entries;
             }
         }
         good_images.push(entry.path());
     }
+    catalogue_pages(path, extras, backs, scale).await;
     let good_images = good_images.iter().map(|n| n.as_path());
-    let mut cropped = crop_images(good_images, &path.join("crop"), 2233, 3093, 76, 76).await;
+    let mut cropped = crop_images(good_images, &path.join("crop"), 2233, 3093, 76, 76, scale).await;
     let mut cropped_images = Vec::new();
     while let Ok(Some(entry)) = cropped.next_entry().await {
         cropped_images.push(entry.path())
@@ -456,50 +2617,120 @@ async fn acquire_rebellion_without_reversal_set(path: &Path, rwr: &str) {
             [744, 1031, 1489, 2062],
         ],
         65,
+        scale,
     )
     .await;
 }
 
 async fn acquire_midnight_sun_set(path: &Path, ms: &str) {
     let download_path = download_set_pdf(ms, &path.join("download")).await;
+    let scale = sheet_scale(&download_path).await;
     let mut extracted = extract_images(&download_path, &path.join("extract")).await;
     let mut good_images = Vec::new();
+    let mut extras = Vec::new();
     'entries: while let Ok(Some(entry)) = extracted.next_entry().await {
         let file_name = entry.file_name().into_string().unwrap();
         for i in 0..=9 {
             if file_name.starts_with(&format!("x-{:>03}", i)) {
+                extras.push(entry.path());
                 continue 'entries;
             }
         }
         if file_name.starts_with("x-078") {
+            extras.push(entry.path());
             continue;
         }
         if file_name.starts_with("x-079") {
+            extras.push(entry.path());
             continue;
         }
         good_images.push(entry.path());
     }
+    catalogue_pages(path, extras, Vec::new(), scale).await;
     let good_images = good_images.iter().map(|n| n.as_path());
     shift_offset_cards(good_images, &path.join("cut"), 3, 1, 68).await;
 }
 
 async fn acquire_parhelion_set(path: &Path, ph: &str) {
     let download_path = download_set_pdf(ph, &path.join("download")).await;
+    let scale = sheet_scale(&download_path).await;
     let mut extracted = extract_images(&download_path, &path.join("extract")).await;
     let mut good_images = Vec::new();
+    let mut extras = Vec::new();
     'entries: while let Ok(Some(entry)) = extracted.next_entry().await {
         let file_name = entry.file_name().into_string().unwrap();
         for i in 29..=33 {
             if file_name.starts_with(&format!("x-{:>03}", i)) {
+                extras.push(entry.path());
                 continue 'entries;
             }
         }
         good_images.push(entry.path());
     }
+    catalogue_pages(path, extras, Vec::new(), scale).await;
     let good_images = good_images.iter().map(|n| n.as_path());
     shift_offset_cards(good_images, &path.join("cut"), 0, 66, 63).await;
 }
 
+/// Records the pages of a set that aren't cards (tokens, credits, reference
+/// material, ...) and the card back pages in the set's `manifest.json`, so
+/// they can be printed too, along with the scale of the sheets.
+async fn catalogue_pages(path: &Path, extras: Vec<PathBuf>, backs: Vec<PathBuf>, scale: u32) {
+    let pages = |mut pages: Vec<PathBuf>| {
+        pages.sort();
+        pages
+            .iter()
+            .map(|page| {
+                let image = page.strip_prefix(path).unwrap();
+                let page = page.file_stem().unwrap().to_str().unwrap();
+                serde_json::json!({ "page": page, "image": image })
+            })
+            .collect::<Vec<_>>()
+    };
+    let manifest = serde_json::json!({
+        "extras": pages(extras),
+        "backs": pages(backs),
+        "scale": scale,
+    });
+    fs::write(
+        path.join("manifest.json"),
+        serde_json::to_string_pretty(&manifest).unwrap(),
+    )
+    .await
+    .unwrap();
+}
+
+/// Finds the extra pages selected with `<set>/<page>`, or all of a set's
+/// extra pages with just `<set>`, relative to the card dir.
+async fn find_extras(path: &Path, selection: &str) -> Vec<PathBuf> {
+    let (set, page) = match selection.split_once('/') {
+        Some((set, page)) => (set, Some(page)),
+        None => (selection, None),
+    };
+    let Ok(read) = fs::read(path.join(set).join("manifest.json")).await else {
+        println!(
+            "{} is not an extra page in {}/manifest.json",
+            selection, set
+        );
+        return Vec::new();
+    };
+    let manifest: serde_json::Value = serde_json::from_slice(&read).unwrap();
+    let extras: Vec<PathBuf> = manifest["extras"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|extra| page.is_none() || extra["page"].as_str() == page)
+        .map(|extra| Path::new(set).join(extra["image"].as_str().unwrap()))
+        .collect();
+    if extras.is_empty() {
+        println!(
+            "{} is not an extra page in {}/manifest.json",
+            selection, set
+        );
+    }
+    extras
+}
+
 async fn shift_offset_cards(
     images: impl Iterator<Item = &Path>,
     to: &Path,
@@ -526,6 +2757,140 @@ async fn shift_offset_cards(
             .await
             .unwrap();
     }
+    check_cuts(to).await;
+}
+
+/// Looks for crop problems in a folder of cuts: blank cells, uneven borders,
+/// slivers of neighbouring cards at the edges and duplicate cards. Problems
+/// are printed and written to `quality.txt` next to the folder.
+async fn check_cuts(cut: &Path) {
+    const WIDTH: usize = 186;
+    const HEIGHT: usize = 258;
+    let mut cuts = Vec::new();
+    let mut entries = fs::read_dir(cut).await.unwrap();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let file_name = entry.file_name().into_string().unwrap();
+        if file_name.ends_with(".png") {
+            cuts.push(file_name);
+        }
+    }
+    cuts.sort();
+
+    let mut report = Vec::new();
+    let mut hashes: Vec<(String, u64)> = Vec::new();
+    for file_name in cuts {
+        let pixels = tokio::process::Command::new("magick")
+            .arg(cut.join(&file_name))
+            .arg("-resize")
+            .arg(format!("{}x{}!", WIDTH, HEIGHT))
+            .arg("-colorspace")
+            .arg("Gray")
+            .arg("-depth")
+            .arg("8")
+            .arg("gray:-")
+            .output()
+            .await
+            .unwrap()
+            .stdout;
+        if pixels.len() != WIDTH * HEIGHT {
+            report.push(format!("FAIL {} could not be read", file_name));
+            continue;
+        }
+        let at = |x: usize, y: usize| pixels[y * WIDTH + x] as f64;
+        let row = |y: usize| (0..WIDTH).map(|x| at(x, y)).sum::<f64>() / WIDTH as f64;
+        let col = |x: usize| (0..HEIGHT).map(|y| at(x, y)).sum::<f64>() / HEIGHT as f64;
+
+        let mean = pixels.iter().map(|p| *p as f64).sum::<f64>() / pixels.len() as f64;
+        let deviation = (pixels
+            .iter()
+            .map(|p| (*p as f64 - mean).powi(2))
+            .sum::<f64>()
+            / pixels.len() as f64)
+            .sqrt();
+        if deviation < 4.0 {
+            report.push(format!("FAIL {} is blank or nearly blank", file_name));
+            continue;
+        }
+
+        // A strong change just inside an edge is a strip of another card
+        let edges = [
+            ("top", row(0), row(3)),
+            ("bottom", row(HEIGHT - 1), row(HEIGHT - 4)),
+            ("left", col(0), col(3)),
+            ("right", col(WIDTH - 1), col(WIDTH - 4)),
+        ];
+        for (edge, outer, inner) in edges {
+            if (outer - inner).abs() > 40.0 {
+                report.push(format!(
+                    "WARN {} has a sliver of a neighbouring card on the {} edge",
+                    file_name, edge
+                ));
+            }
+        }
+
+        // Border thickness is how far in the edge's shade carries on
+        let border = |line: &dyn Fn(usize) -> f64, limit: usize| {
+            let edge = line(0);
+            (1..limit)
+                .take_while(|i| (line(*i) - edge).abs() < 12.0)
+                .count()
+        };
+        let top = border(&|i| row(i), HEIGHT / 4);
+        let bottom = border(&|i| row(HEIGHT - 1 - i), HEIGHT / 4);
+        let left = border(&|i| col(i), WIDTH / 4);
+        let right = border(&|i| col(WIDTH - 1 - i), WIDTH / 4);
+        if top.abs_diff(bottom) > 4 || left.abs_diff(right) > 4 {
+            report.push(format!(
+                "WARN {} has uneven borders (top {}, bottom {}, left {}, right {})",
+                file_name, top, bottom, left, right
+            ));
+        }
+
+        // Difference hash from a 9x8 grid of block averages
+        let block = |bx: usize, by: usize| {
+            let (x0, x1) = (bx * WIDTH / 9, (bx + 1) * WIDTH / 9);
+            let (y0, y1) = (by * HEIGHT / 8, (by + 1) * HEIGHT / 8);
+            let mut total = 0.0;
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    total += at(x, y);
+                }
+            }
+            total / ((x1 - x0) * (y1 - y0)) as f64
+        };
+        let mut hash = 0u64;
+        for by in 0..8 {
+            for bx in 0..8 {
+                hash = hash << 1 | (block(bx, by) < block(bx + 1, by)) as u64;
+            }
+        }
+        for (other, other_hash) in hashes.iter() {
+            if (hash ^ other_hash).count_ones() <= 4 {
+                report.push(format!(
+                    "WARN {} looks like a duplicate of {}",
+                    file_name, other
+                ));
+            }
+        }
+        hashes.push((file_name, hash));
+    }
+
+    let failures = report
+        .iter()
+        .filter(|line| line.starts_with("FAIL"))
+        .count();
+    println!(
+        "{:?} checked, {} failures and {} warnings",
+        cut,
+        failures,
+        report.len() - failures
+    );
+    for line in report.iter() {
+        println!("  {}", line);
+    }
+    fs::write(cut.parent().unwrap().join("quality.txt"), report.join("\n"))
+        .await
+        .unwrap();
 }
 
 async fn download_set_pdf(url: &str, path: &Path) -> PathBuf {
@@ -552,6 +2917,32 @@ async fn download_back_raw(url: &str, path: &Path) -> PathBuf {
     path
 }
 
+/// How many times the resolution of the standard 1x printable sheets the
+/// sheets in a PDF are, from the resolution `pdfimages` reports for its first
+/// page sized image. 1x sheets are 300 ppi, 2x sheets 600 ppi and so on.
+async fn sheet_scale(pdf: &Path) -> u32 {
+    let output = tokio::process::Command::new("pdfimages")
+        .arg("-list")
+        .arg(pdf)
+        .output()
+        .await
+        .unwrap();
+    let list = String::from_utf8_lossy(&output.stdout);
+    let ppi = list
+        .lines()
+        .skip(2)
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .filter(|columns| columns.len() > 12 && columns[2] == "image")
+        .find(|columns| columns[3].parse::<u32>().is_ok_and(|width| width >= 2000))
+        .and_then(|columns| columns[12].parse::<f64>().ok())
+        .unwrap_or(300.0);
+    let scale = ((ppi / 300.0).round() as u32).max(1);
+    if scale > 1 {
+        println!("{:?} is a {}x sheet ({} ppi)", pdf, scale, ppi);
+    }
+    scale
+}
+
 async fn extract_images(from: &Path, to: &Path) -> fs::ReadDir {
     if matches!(fs::try_exists(&to).await, Ok(true)) {
         println!("{:?} already extracted, skipping", from);
@@ -561,7 +2952,7 @@ async fn extract_images(from: &Path, to: &Path) -> fs::ReadDir {
     tokio::process::Command::new("pdfimages")
         .arg("-png")
         .arg(from)
-        .arg(&to.join("x"))
+        .arg(to.join("x"))
         .spawn()
         .unwrap()
         .wait()
@@ -570,6 +2961,8 @@ async fn extract_images(from: &Path, to: &Path) -> fs::ReadDir {
     fs::read_dir(to).await.unwrap()
 }
 
+/// Crops every sheet to the card grid. The geometry is given for 1x sheets
+/// and multiplied by `scale` for higher resolution ones.
 async fn crop_images(
     from: impl Iterator<Item = &Path>,
     to: &Path,
@@ -577,6 +2970,7 @@ async fn crop_images(
     height: u32,
     top: u32,
     left: u32,
+    scale: u32,
 ) -> fs::ReadDir {
     if matches!(fs::try_exists(&to).await, Ok(true)) {
         println!("{:?} crops already generated, skipping", to);
@@ -588,10 +2982,10 @@ async fn crop_images(
         crop_raw(
             image,
             &to.join(format!("c-{:>03}.png", i)),
-            width,
-            height,
-            left,
-            top,
+            width * scale,
+            height * scale,
+            left * scale,
+            top * scale,
         )
         .await;
     }
@@ -599,11 +2993,14 @@ async fn crop_images(
     fs::read_dir(to).await.unwrap()
 }
 
+/// Cuts the cards out of the cropped sheets. Like `crop_images`, the cutmap
+/// is for 1x sheets and is multiplied by `scale`.
 async fn cutout_images(
     from: impl Iterator<Item = &Path>,
     to: &Path,
     cutmap: &[[u32; 4]],
     offset: i32,
+    scale: u32,
 ) {
     if matches!(fs::try_exists(&to).await, Ok(true)) {
         println!("{:?} cutouts already generated, skipping", to);
@@ -614,7 +3011,7 @@ async fn cutout_images(
     let mut index = 1;
     for image in from {
         for cutout in cutmap {
-            let [width, height, left, top] = *cutout;
+            let [width, height, left, top] = cutout.map(|n| n * scale);
             crop_raw(
                 image,
                 &to.join(format!("c-{:>03}.png", index + offset)),
@@ -627,6 +3024,7 @@ async fn cutout_images(
             index += 1;
         }
     }
+    check_cuts(to).await;
 }
 
 async fn crop_raw(from: &Path, to: &Path, width: u32, height: u32, left: u32, top: u32) {
@@ -644,6 +3042,37 @@ async fn crop_raw(from: &Path, to: &Path, width: u32, height: u32, left: u32, to
         .unwrap();
 }
 
+async fn resize_raw(from: &Path, to: &Path, width: u32, height: u32) {
+    tokio::process::Command::new("magick")
+        .arg("convert")
+        .arg(from)
+        .arg("-filter")
+        .arg("Lanczos")
+        .arg("-resize")
+        .arg(format!("{}x{}!", width, height))
+        .arg(to)
+        .spawn()
+        .unwrap()
+        .wait()
+        .await
+        .unwrap();
+}
+
+/// The average red, green and blue of an image, from 0 to 1.
+async fn mean_colour(image: &Path) -> [f64; 3] {
+    let output = tokio::process::Command::new("magick")
+        .arg(image)
+        .arg("-format")
+        .arg("%[fx:mean.r] %[fx:mean.g] %[fx:mean.b]")
+        .arg("info:")
+        .output()
+        .await
+        .unwrap();
+    let output = String::from_utf8(output.stdout).unwrap();
+    let mut channels = output.split_whitespace().map(|c| c.parse().unwrap());
+    [(); 3].map(|_| channels.next().unwrap())
+}
+
 async fn merge_raw(former: &Path, latter: &Path, output: &Path, horizontal: bool) {
     let mut magick = tokio::process::Command::new("magick");
     let mut convert = magick.arg("convert");
@@ -662,3 +3091,268 @@ async fn merge_raw(former: &Path, latter: &Path, output: &Path, horizontal: bool
         .await
         .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(code: &str) -> Card {
+        Card {
+            code: code.to_string(),
+            data: serde_json::json!({ "code": code }),
+            image: Some(PathBuf::from(format!("{}.png", code))),
+            back: None,
+            fallback: None,
+        }
+    }
+
+    fn print(deck: usize, code: &str, count: i64) -> Print {
+        Print {
+            deck,
+            card: card(code),
+            count,
+        }
+    }
+
+    fn pooled(prints: &[Print]) -> Vec<(usize, &str, i64)> {
+        prints
+            .iter()
+            .map(|print| (print.deck, print.card.code.as_str(), print.count))
+            .collect()
+    }
+
+    #[test]
+    fn pool_decks_takes_highest_count() {
+        let prints = vec![
+            print(0, "30010", 2),
+            print(0, "30011", 1),
+            print(1, "30010", 3),
+            print(1, "30012", 1),
+            print(2, "30011", 1),
+        ];
+        assert_eq!(
+            pooled(&pool_decks(prints, None)),
+            vec![(0, "30010", 3), (0, "30011", 1), (1, "30012", 1)]
+        );
+    }
+
+    #[test]
+    fn pool_decks_total() {
+        let prints = vec![print(0, "30010", 3), print(1, "30011", 1)];
+        assert_eq!(
+            pooled(&pool_decks(prints, Some(2))),
+            vec![(0, "30010", 2), (1, "30011", 2)]
+        );
+    }
+
+    #[test]
+    fn pool_decks_keeps_first_deck() {
+        let prints = vec![print(1, "30010", 1), print(0, "30010", 3)];
+        assert_eq!(pooled(&pool_decks(prints, None)), vec![(1, "30010", 3)]);
+    }
+
+    fn counts(cards: &[(&str, i64)]) -> Vec<(String, i64)> {
+        cards
+            .iter()
+            .map(|(card, count)| (card.to_string(), *count))
+            .collect()
+    }
+
+    /// How many of each current card a diff prints.
+    fn diff_prints(previous: &[(&str, i64)], current: &[(&str, i64)]) -> Vec<(String, i64)> {
+        let (mut kept, _) = diff_counts(&counts(previous), &counts(current));
+        let mut unchanged = Vec::new();
+        current
+            .iter()
+            .map(|(code, count)| {
+                let count = take_owned(&mut kept, &card(code), *count, &mut unchanged);
+                (code.to_string(), count)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn diff_prints_added_cards() {
+        assert_eq!(
+            diff_prints(&[("30010", 2)], &[("30010", 2), ("30011", 3)]),
+            counts(&[("30010", 0), ("30011", 3)])
+        );
+    }
+
+    #[test]
+    fn diff_prints_increase() {
+        assert_eq!(
+            diff_prints(&[("30010", 1)], &[("30010", 3)]),
+            counts(&[("30010", 2)])
+        );
+    }
+
+    #[test]
+    fn diff_lists_removed_and_decreased_cards() {
+        let (_, removed) = diff_counts(
+            &counts(&[("30010", 3), ("30011", 2), ("30012", 1)]),
+            &counts(&[("30010", 1), ("30012", 1)]),
+        );
+        assert_eq!(removed, counts(&[("30010", 2), ("30011", 2)]));
+    }
+
+    #[test]
+    fn diff_unchanged_deck() {
+        let deck = [("30010", 3), ("30011", 1)];
+        let (_, removed) = diff_counts(&counts(&deck), &counts(&deck));
+        assert!(removed.is_empty());
+        assert_eq!(
+            diff_prints(&deck, &deck),
+            counts(&[("30010", 0), ("30011", 0)])
+        );
+    }
+
+    #[test]
+    fn diff_counts_cards_across_decks() {
+        let (_, removed) = diff_counts(
+            &counts(&[("30010", 3)]),
+            &counts(&[("30010", 1), ("30010", 1)]),
+        );
+        assert_eq!(removed, counts(&[("30010", 1)]));
+    }
+
+    #[test]
+    fn diff_then_collection() {
+        let (mut kept, _) = diff_counts(&counts(&[("30010", 1)]), &counts(&[("30010", 3)]));
+        let mut owned = HashMap::from([("30010".to_string(), 1)]);
+        let (mut unchanged, mut skipped) = (Vec::new(), Vec::new());
+        let card = card("30010");
+        let count = take_owned(&mut kept, &card, 3, &mut unchanged);
+        let count = take_owned(&mut owned, &card, count, &mut skipped);
+        assert_eq!(count, 1);
+        assert_eq!(unchanged.len(), 1);
+        assert_eq!(skipped.len(), 1);
+    }
+
+    /// The sheet's HTML between the body tags.
+    fn body(sheet: Sheet) -> String {
+        let document = sheet.finish();
+        let start = document.find("<body>").unwrap() + "<body>".len();
+        document[start..document.len() - "</body></html>".len()].to_string()
+    }
+
+    fn imgs(images: &[&str]) -> String {
+        images
+            .iter()
+            .map(|image| match *image {
+                "" => "<div></div>".to_string(),
+                image => format!("<img src=\"{}\" />", image),
+            })
+            .collect()
+    }
+
+    fn page(images: &[&str]) -> String {
+        format!(
+            "<div class=\"page\"><div class=\"imgs\">{}</div></div>",
+            imgs(images)
+        )
+    }
+
+    #[test]
+    fn sheet_duplex_partial_page() {
+        let mut sheet = Sheet::new(true);
+        sheet.push_image(Path::new("f1"), Some(Path::new("b1")), None);
+        sheet.push_image(Path::new("f2"), Some(Path::new("b2")), None);
+        assert_eq!(
+            body(sheet),
+            page(&["f1", "f2"]) + &page(&["", "b2", "b1", "", "", "", "", "", ""])
+        );
+    }
+
+    #[test]
+    fn sheet_duplex_full_page() {
+        let mut sheet = Sheet::new(true);
+        for i in 1..=10 {
+            let (front, back) = (format!("f{}", i), format!("b{}", i));
+            sheet.push_image(Path::new(&front), Some(Path::new(&back)), None);
+        }
+        let fronts = ["f1", "f2", "f3", "f4", "f5", "f6", "f7", "f8", "f9"];
+        let backs = ["b3", "b2", "b1", "b6", "b5", "b4", "b9", "b8", "b7"];
+        let last_backs = ["", "", "b10", "", "", "", "", "", ""];
+        assert_eq!(
+            body(sheet),
+            page(&fronts) + &page(&backs) + &page(&["f10"]) + &page(&last_backs)
+        );
+    }
+
+    #[test]
+    fn sheet_break_page() {
+        let mut sheet = Sheet::new(false);
+        sheet.break_page();
+        sheet.push_image(Path::new("f1"), None, None);
+        sheet.break_page();
+        sheet.break_page();
+        sheet.push_image(Path::new("f2"), None, None);
+        assert_eq!(body(sheet), page(&["f1"]) + &page(&["f2"]));
+    }
+
+    #[test]
+    fn sheet_duplex_push_page_has_blank_back() {
+        let mut sheet = Sheet::new(true);
+        sheet.push_image(Path::new("f1"), Some(Path::new("b1")), None);
+        sheet.push_page("cover");
+        sheet.push_image(Path::new("f2"), None, None);
+        assert_eq!(
+            body(sheet),
+            page(&["f1"])
+                + &page(&["", "", "b1", "", "", "", "", "", ""])
+                + "<div class=\"page sheet\">cover</div><div class=\"page\"></div>"
+                + &page(&["f2"])
+                + &page(&["", "", "", "", "", "", "", "", ""])
+        );
+    }
+
+    #[test]
+    fn sheet_fold_keeps_faces_in_a_row() {
+        let mut sheet = Sheet::new(false);
+        sheet.push_image(Path::new("f1"), None, None);
+        sheet.push_image(Path::new("f2"), None, None);
+        sheet.push_folded(Path::new("front"), Path::new("back"), None);
+        assert_eq!(body(sheet), page(&["f1", "f2", "", "front", "back"]));
+    }
+
+    #[test]
+    fn cut_position_parses_cut_names() {
+        assert_eq!(cut_position("c-007.png"), Some(7));
+        assert_eq!(cut_position("c-123.png"), Some(123));
+        assert_eq!(cut_position("30010.png"), None);
+        assert_eq!(cut_position("c-007.jpg"), None);
+    }
+
+    #[test]
+    fn parse_deck_nrdb_shape() {
+        let json = serde_json::json!({
+            "data": [{
+                "id": 71234,
+                "name": "Hoshiko",
+                "cards": { "30010": 1, "30011": 3 }
+            }]
+        });
+        let deck = parse_deck("file", &json);
+        assert_eq!(deck.label, "file");
+        assert_eq!(deck.name, "Hoshiko");
+        assert_eq!(deck.nrdb_id.as_deref(), Some("71234"));
+        assert_eq!(
+            deck.cards,
+            vec![("30010".to_string(), 1), ("30011".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn parse_deck_count_map() {
+        let json = serde_json::json!({ "30010": 1, "30011": 3 });
+        let deck = parse_deck("file", &json);
+        assert_eq!(deck.label, "file");
+        assert_eq!(deck.name, "file");
+        assert_eq!(deck.nrdb_id, None);
+        assert_eq!(
+            deck.cards,
+            vec![("30010".to_string(), 1), ("30011".to_string(), 3)]
+        );
+    }
+}